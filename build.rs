@@ -0,0 +1,25 @@
+//! Records the version of `bevy_harmonize_api` the loader supports
+//!
+//! The api can't be a dependency of the loader, since it sets the global allocator and panic
+//! handler of mods, so its version is read from its manifest instead
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let api_manifest = Path::new(&manifest_dir).join("crates/api/Cargo.toml");
+    println!("cargo:rerun-if-changed={}", api_manifest.display());
+
+    let contents = fs::read_to_string(&api_manifest).expect("Failed to read the api manifest");
+    let version = contents
+        .lines()
+        .find_map(|line| {
+            let value = line
+                .strip_prefix("version")?
+                .trim_start()
+                .strip_prefix('=')?;
+            value.trim().strip_prefix('"')?.strip_suffix('"')
+        })
+        .expect("The api manifest has no version");
+    println!("cargo:rustc-env=BEVY_HARMONIZE_API_VERSION={}", version);
+}
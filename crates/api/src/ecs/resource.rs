@@ -1,12 +1,20 @@
+use core::any::TypeId;
+
 use bevy_reflect::{
-    serde::TypedReflectSerializer, FromReflect, GetTypeRegistration, TypeRegistry, Typed,
+    serde::TypedReflectSerializer, FromReflect, GetTypeRegistration, PartialReflect, ReflectRef,
+    TypeRegistry, Typed,
 };
+use common::FieldLayout;
 
 extern crate alloc;
 use alloc::vec::Vec;
 
 use super::Addressable;
 
+/// A value shared by the systems of a mod, which the host keeps across reloads
+///
+/// The host lays out resources in memory, so they may only be made of structs, tuples, arrays
+/// and primitives. Mods with resources holding enums, collections or other types fail to build.
 pub trait Resource
 where
     Self: Sized + Typed + FromReflect + GetTypeRegistration + Addressable,
@@ -21,6 +29,15 @@ where
 
         bincode::serde::encode_to_vec(&serializer, bincode::config::standard()).unwrap()
     }
+
+    /// The memory layout of every struct-like type within the default value
+    fn default_value_layouts() -> Vec<(TypeId, Vec<FieldLayout>)> {
+        let value = Self::default_value();
+
+        let mut layouts = Vec::new();
+        collect_layouts(value.as_partial_reflect(), &mut layouts);
+        layouts
+    }
 }
 
 impl<R> Resource for R
@@ -31,3 +48,35 @@ where
         Self::default()
     }
 }
+
+/// Records where each field lives relative to its parent, recursing into fields
+fn collect_layouts(value: &dyn PartialReflect, layouts: &mut Vec<(TypeId, Vec<FieldLayout>)>) {
+    let fields: Vec<&dyn PartialReflect> = match value.reflect_ref() {
+        ReflectRef::Struct(value) => value.iter_fields().collect(),
+        ReflectRef::TupleStruct(value) => value.iter_fields().collect(),
+        ReflectRef::Tuple(value) => value.iter_fields().collect(),
+        _ => return,
+    };
+
+    let Some(type_info) = value.get_represented_type_info() else {
+        return;
+    };
+
+    let base = address_of(value);
+    let layout = fields
+        .iter()
+        .map(|field| FieldLayout {
+            offset: address_of(*field) - base,
+            size: size_of_val(*field),
+        })
+        .collect();
+    layouts.push((type_info.type_id(), layout));
+
+    for field in fields {
+        collect_layouts(field, layouts);
+    }
+}
+
+fn address_of(value: &dyn PartialReflect) -> usize {
+    value as *const dyn PartialReflect as *const u8 as usize
+}
@@ -0,0 +1,14 @@
+//! Run conditions for the most common cases, similar to bevy's `common_conditions`
+//!
+//! Conditions are exported by name, so unlike bevy's these can't capture values
+
+use super::Res;
+use crate::ecs::Resource;
+
+/// Runs systems when the resource changed since the condition last ran
+pub fn resource_changed<T>(res: Res<T>) -> bool
+where
+    T: Resource,
+{
+    res.is_changed()
+}
@@ -0,0 +1,19 @@
+use super::{function_system::SystemParamFunction, system_param::ReadOnlySystemParam, IntoSystem};
+
+/// A system deciding whether other systems run, see [`super::IntoSchedule::run_if`]
+///
+/// Like in bevy, conditions can only read data
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid run condition",
+    label = "invalid run condition",
+    note = "run conditions return `bool` and only take read-only params such as `Res`"
+)]
+pub trait Condition<Marker>: IntoSystem<(), bool, Marker> + Copy {}
+
+impl<Marker, F> Condition<Marker> for F
+where
+    Marker: 'static,
+    F: SystemParamFunction<Marker, In = (), Out = bool> + Copy,
+    F::Param: ReadOnlySystemParam,
+{
+}
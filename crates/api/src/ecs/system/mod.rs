@@ -1,3 +1,5 @@
+pub mod common_conditions;
+mod condition;
 mod function_system;
 mod params;
 mod schedule;
@@ -7,10 +9,11 @@ mod system_set;
 
 use core::ops::{Deref, DerefMut};
 
+pub use condition::Condition;
 pub use function_system::FunctionSystem;
 pub use params::*;
 pub use schedule::{IntoSchedule, Schedule};
-pub use system::System;
+pub use system::{System, SystemOutput};
 pub use system_param::SystemParam;
 pub use system_set::IntoSystemSet;
 
@@ -30,6 +30,7 @@ impl<'a> SystemParam for Commands<'a> {
 
 /// Similar to bevy_ecs::system::commands::Commands
 impl<'a> Commands<'a> {
+    /// Requires the mod to declare [`crate::schema::Capability::SpawnEntities`]
     pub fn spawn_empty(&mut self) -> EntityCommands<'a> {
         let id = unsafe { crate::external::spawn_empty() };
         EntityCommands(id, PhantomData)
@@ -7,10 +7,82 @@ use alloc::vec;
 use common::StableId;
 
 use crate::ecs::{
-    system::{system_param::Params, SystemParam},
+    system::{
+        system_param::{Params, ReadOnlySystemParam},
+        SystemParam,
+    },
     Resource,
 };
 
+/// Reads a [`Resource`], which may only be made of structs, tuples, arrays and primitives
+pub struct Res<'w, T>
+where
+    T: Resource,
+{
+    phantom: PhantomData<&'w T>,
+}
+
+impl<'a, T> SystemParam for Res<'a, T>
+where
+    T: Resource,
+{
+    type State = ();
+    type Item<'state> = Res<'state, T>;
+
+    fn init_state() -> Self::State {
+        ()
+    }
+
+    fn get_param<'state>(_: &'state mut Self::State) -> Self::Item<'state> {
+        Res {
+            phantom: PhantomData,
+        }
+    }
+
+    fn get_metadata() -> Params {
+        vec![common::Param::Res {
+            mutable: false,
+            id: StableId::from_typed::<T>(),
+        }]
+    }
+}
+
+impl<'a, T> ReadOnlySystemParam for Res<'a, T> where T: Resource {}
+
+impl<'w, T> Res<'w, T>
+where
+    T: Resource,
+{
+    /// Whether the resource changed since the system last ran
+    pub fn is_changed(&self) -> bool {
+        unsafe { crate::external::is_component_changed(T::COMPONENT_ID) != 0 }
+    }
+}
+
+impl<'w, T> Deref for Res<'w, T>
+where
+    T: Resource,
+{
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*T::PTR }
+    }
+}
+
+impl<'w, T> AsRef<T> for Res<'w, T>
+where
+    T: Resource,
+{
+    #[inline]
+    fn as_ref(&self) -> &T {
+        self.deref()
+    }
+}
+
+/// Reads and changes a [`Resource`], which may only be made of structs, tuples, arrays and
+/// primitives
 pub struct ResMut<'w, T>
 where
     T: Resource,
@@ -39,12 +111,22 @@ where
 
     fn get_metadata() -> Params {
         vec![common::Param::Res {
-            mutable: false,
+            mutable: true,
             id: StableId::from_typed::<T>(),
         }]
     }
 }
 
+impl<'w, T> ResMut<'w, T>
+where
+    T: Resource,
+{
+    /// Whether the resource changed since the system last ran, or was changed by it
+    pub fn is_changed(&self) -> bool {
+        self.changed || unsafe { crate::external::is_component_changed(T::COMPONENT_ID) != 0 }
+    }
+}
+
 impl<'w, T> Deref for ResMut<'w, T>
 where
     T: Resource,
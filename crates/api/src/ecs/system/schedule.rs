@@ -2,7 +2,7 @@ use crate::ecs::Reflected;
 
 use super::{
     system_set::{SystemSet, Systems},
-    IntoSystemSet,
+    Condition, IntoSystem, IntoSystemSet,
 };
 use common::StableId;
 use const_vec::ConstVec;
@@ -47,6 +47,17 @@ where
             .push(Constraint::Includes(stable_id_getter(named_system_set)));
         schedule
     }
+
+    /// Only runs the systems when the condition returns `true`
+    ///
+    /// The condition is run once per schedule run, before the first of the systems
+    fn run_if<Marker>(self, condition: impl Condition<Marker>) -> Schedule {
+        let mut schedule = self.into_schedule();
+        schedule
+            .constraints
+            .push(Constraint::Condition(condition_getter(condition)));
+        schedule
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -65,6 +76,7 @@ impl const IntoSchedule<()> for Schedule {
 impl Schedule {
     pub(crate) fn build(self) -> common::Schedule {
         let mut constraints = Vec::new();
+        let mut systems = (self.systems_getter)().0;
 
         for constraint in self.constraints.into_slice() {
             match constraint {
@@ -110,11 +122,26 @@ impl Schedule {
                         });
                     }
                 }
+                Constraint::Condition(condition_getter) => {
+                    let condition = condition_getter();
+                    let sets = (self.system_set_getter)().into_min_sets();
+                    for set in sets {
+                        constraints.push(common::Constraint::Condition {
+                            set,
+                            condition: condition.id,
+                        });
+                    }
+
+                    // Conditions are exported like any other system
+                    if !systems.iter().any(|system| system.id == condition.id) {
+                        systems.push(condition);
+                    }
+                }
             }
         }
 
         common::Schedule {
-            systems: (self.systems_getter)().0,
+            systems,
             constraints,
         }
     }
@@ -126,6 +153,7 @@ enum Constraint {
     Before(fn() -> SystemSet),
     After(fn() -> SystemSet),
     Includes(fn() -> StableId),
+    Condition(fn() -> common::System),
 }
 
 #[inline]
@@ -136,6 +164,14 @@ where
     T::into_system_set
 }
 
+#[inline]
+const fn condition_getter<T, Marker>(_condition: T) -> fn() -> common::System
+where
+    T: IntoSystem<(), bool, Marker>,
+{
+    T::into_metadata
+}
+
 #[inline]
 const fn stable_id_getter<T>(_typed: T) -> fn() -> StableId
 where
@@ -288,6 +324,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn run_if() {
+        fn system1() {}
+        fn system2() {}
+        fn condition() -> bool {
+            true
+        }
+
+        const SCHEDULE: Schedule = (system1, system2).run_if(condition);
+
+        let schedule = SCHEDULE.build();
+        let condition_metadata = into_metadata(condition);
+        assert_eq!(
+            schedule.constraints,
+            vec![common::Constraint::Condition {
+                set: common::SystemSet::Anonymous(vec![
+                    into_metadata(system1).id,
+                    into_metadata(system2).id,
+                ]),
+                condition: condition_metadata.id,
+            }]
+        );
+        assert_eq!(schedule.systems.last(), Some(&condition_metadata));
+        assert_eq!(schedule.systems.len(), 3);
+    }
+
     #[test]
     fn in_set() {
         fn system() {}
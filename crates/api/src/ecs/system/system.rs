@@ -13,3 +13,21 @@ where
     /// Runs the system with the given input
     fn run(&mut self, input: Self::In) -> Self::Out;
 }
+
+/// Outputs of systems that can be returned to the modloader
+pub trait SystemOutput {
+    fn into_raw(self) -> u32;
+}
+
+impl SystemOutput for () {
+    fn into_raw(self) -> u32 {
+        0
+    }
+}
+
+/// Returned by run conditions
+impl SystemOutput for bool {
+    fn into_raw(self) -> u32 {
+        self as u32
+    }
+}
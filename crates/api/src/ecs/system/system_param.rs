@@ -25,6 +25,9 @@ pub trait SystemParam: Sized {
     fn get_metadata() -> Params;
 }
 
+/// A [`SystemParam`] that only reads data, so it can be used by run conditions
+pub trait ReadOnlySystemParam: SystemParam {}
+
 /// Shorthand way of accessing the associated type [`SystemParam::Item`] for a given [`SystemParam`].
 pub type SystemParamItem<'s, P> = <P as SystemParam>::Item<'s>;
 
@@ -63,3 +66,11 @@ macro_rules! impl_system_param_tuple {
 }
 
 all_tuples!(impl_system_param_tuple, 0, 16, P);
+
+macro_rules! impl_read_only_system_param_tuple {
+    ($($param: ident),*) => {
+        impl<$($param: ReadOnlySystemParam),*> ReadOnlySystemParam for ($($param,)*) {}
+    };
+}
+
+all_tuples!(impl_read_only_system_param_tuple, 0, 16, P);
@@ -7,4 +7,6 @@ extern "C" {
 
     pub fn flag_component_changed(component_id: usize);
 
+    pub fn is_component_changed(component_id: usize) -> u32;
+
 }
@@ -8,19 +8,25 @@ pub mod ecs;
 pub mod panic;
 pub mod schema;
 
+/// The version of the api, recorded in the manifest of every mod built against it
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub mod prelude {
     pub use bevy_reflect::prelude::*;
     pub use bevy_reflect_derive::*;
     pub use tracing::{debug, error, info, trace, warn};
 
     pub use crate::ecs::{
-        system::{Commands, IntoSchedule, IntoSystem, IntoSystemSet, ResMut},
+        system::{
+            common_conditions::*, Commands, Condition, IntoSchedule, IntoSystem, IntoSystemSet,
+            Res, ResMut,
+        },
         Addressable, Reflected, Resource,
     };
-    pub use crate::schema::{Mod, Schema};
+    pub use crate::schema::{Capability, Mod, Schema};
 
     // Schedules
-    pub use common::{Start, Update};
+    pub use common::{FixedUpdate, Last, PostStart, PostUpdate, PreStart, PreUpdate, Start, Update};
 
     pub use derive::Addressable;
 }
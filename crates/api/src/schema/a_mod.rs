@@ -2,7 +2,7 @@ use bevy_reflect::{TypeInfo, Typed};
 
 use crate::ecs::{system::IntoSchedule, Reflected, Resource};
 
-use super::{InnerType, Schema};
+use super::{Capability, Dependency, InnerType, Schema};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Mod {
@@ -16,6 +16,15 @@ impl Mod {
         Self { schema }
     }
 
+    /// Sets the semver version of the mod, which defaults to `0.0.0`
+    ///
+    /// The host prefers newer versions of a mod, and checks the version requirements of
+    /// mods depending on it
+    pub const fn version(&mut self, version: &'static str) -> &mut Self {
+        self.schema.version = version;
+        self
+    }
+
     pub const fn into_schema(self) -> Schema {
         self.schema
     }
@@ -37,9 +46,55 @@ impl Mod {
         R: Resource,
     {
         self.register_type::<R>();
-        self.schema
-            .resources
-            .push((R::type_info, R::default_value_as_buffer));
+        self.schema.resources.push((
+            R::type_info,
+            R::default_value_as_buffer,
+            R::default_value_layouts,
+            self.schema.current_feature,
+        ));
+        self
+    }
+
+    /// Declares a feature of the mod, which the host can enable and disable at runtime.
+    /// Resources and systems added after this call belong to the feature
+    ///
+    /// Declaring a feature that was already declared adds to it again. Panics if the feature
+    /// has the name of the mod, which names the part of the mod outside of any feature
+    pub const fn add_feature(&mut self, name: &'static str) -> &mut Self {
+        if let Some(mod_name) = self.schema.name {
+            if str_eq(mod_name, name) {
+                panic!("A feature can't have the name of the mod");
+            }
+        }
+
+        let features = self.schema.features.into_slice();
+        let mut index = 0;
+        while index < features.len() {
+            if str_eq(features[index], name) {
+                self.schema.current_feature = index + 1;
+                return self;
+            }
+            index += 1;
+        }
+
+        self.schema.features.push(name);
+        self.schema.current_feature = self.schema.features.len();
+        self
+    }
+
+    /// Declares another mod this one needs, either by package name or by a uri it can be
+    /// loaded from, along with a semver requirement on its version
+    ///
+    /// The host starts the dependency first, and refuses to start the mod if it can't
+    pub const fn add_dependency(&mut self, id: &'static str, version: &'static str) -> &mut Self {
+        self.schema.dependencies.push(Dependency { id, version });
+        self
+    }
+
+    /// Declares a capability the mod needs. The host refuses to load the mod unless it
+    /// grants every capability declared
+    pub const fn require_capability(&mut self, capability: Capability) -> &mut Self {
+        self.schema.capabilities.push(capability);
         self
     }
 
@@ -56,21 +111,41 @@ impl Mod {
         }
         let id_getter = type_info(schedule);
         let schedule = systems.into_schedule();
-        self.schema.schedules.push((id_getter, schedule));
+        self.schema
+            .schedules
+            .push((id_getter, schedule, self.schema.current_feature));
         self
     }
 }
 
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut index = 0;
+    while index < a.len() {
+        if a[index] != b[index] {
+            return false;
+        }
+        index += 1;
+    }
+    true
+}
+
 // Tests
 #[cfg(test)]
 mod tests {
     use core::any::TypeId;
 
+    extern crate alloc;
+    use alloc::{vec, vec::Vec};
+
     use crate::ecs::Addressable;
 
     use super::*;
     use bevy_reflect::Reflect;
-    use common::{StableId, Start, Update};
+    use common::{FieldLayout, StableId, Start, Update};
 
     #[test]
     fn name() {
@@ -103,9 +178,17 @@ mod tests {
         assert_eq!(types.len(), 1);
 
         assert_eq!(resources.len(), 1);
-        let (stable_id, default_value) = resources[0];
+        let (stable_id, default_value, layouts, feature) = resources[0];
+        assert_eq!(feature, 0);
         assert_eq!(stable_id().type_path_table().short_path(), "TestResource");
         assert_eq!(default_value(), [123]);
+        assert_eq!(
+            layouts(),
+            [(
+                TypeId::of::<TestResource>(),
+                vec![FieldLayout { offset: 0, size: 4 }]
+            )]
+        );
     }
 
     #[test]
@@ -123,6 +206,80 @@ mod tests {
         assert_eq!(test_type.type_id(), TypeId::of::<TestType>());
     }
 
+    #[test]
+    fn require_capability() {
+        const SCHEMA: Schema = Mod::new("Test require_capability")
+            .require_capability(Capability::SpawnEntities)
+            .require_capability(Capability::HostFunction("play_sound"))
+            .into_schema();
+
+        assert_eq!(
+            SCHEMA.capabilities(),
+            [
+                Capability::SpawnEntities,
+                Capability::HostFunction("play_sound")
+            ]
+        );
+    }
+
+    #[test]
+    fn version() {
+        const SCHEMA: Schema = Mod::new("Test version").version("1.2.3").into_schema();
+        assert_eq!(SCHEMA.version(), "1.2.3");
+        assert_eq!(SCHEMA.api_version(), env!("CARGO_PKG_VERSION"));
+
+        const DEFAULT: Schema = Mod::new("Test version").into_schema();
+        assert_eq!(DEFAULT.version(), "0.0.0");
+    }
+
+    #[test]
+    fn add_dependency() {
+        const SCHEMA: Schema = Mod::new("Test add_dependency")
+            .add_dependency("physics", "^1.2")
+            .add_dependency("file://mods/weather.hmod", "*")
+            .into_schema();
+
+        assert_eq!(
+            SCHEMA.dependencies(),
+            [
+                Dependency {
+                    id: "physics",
+                    version: "^1.2"
+                },
+                Dependency {
+                    id: "file://mods/weather.hmod",
+                    version: "*"
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn add_feature() {
+        fn system1() {}
+        fn system2() {}
+        fn system3() {}
+
+        const SCHEMA: Schema = Mod::new("Test add_feature")
+            .add_systems(Update, system1)
+            .add_feature("particles")
+            .add_systems(Update, system2)
+            .add_feature("sounds")
+            .add_feature("particles")
+            .add_systems(Start, system3)
+            .into_schema();
+
+        assert_eq!(SCHEMA.features(), ["particles", "sounds"]);
+        let features: Vec<_> = SCHEMA.schedules().map(|(_, _, feature)| feature).collect();
+        assert_eq!(features, [0, 1, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn add_feature_named_after_the_mod() {
+        Mod::new("Test add_feature").add_feature("Test add_feature");
+    }
+
     #[test]
     fn add_systems() {
         fn system1() {}
@@ -1,9 +1,12 @@
+use core::any::TypeId;
+
 use const_vec::ConstVec;
 
 extern crate alloc;
 use alloc::vec::Vec;
 
 use bevy_reflect::TypeInfo;
+use common::FieldLayout;
 
 mod a_mod;
 pub use a_mod::Mod;
@@ -13,18 +16,50 @@ use crate::ecs::system::Schedule;
 #[derive(Debug, Clone, Copy)]
 pub struct Schema {
     pub(crate) name: Option<&'static str>,
+    pub(crate) version: &'static str,
     pub(crate) types: ConstVec<InnerType, 1024>,
-    pub(crate) resources: ConstVec<(fn() -> &'static TypeInfo, fn() -> Vec<u8>), 128>,
-    pub(crate) schedules: ConstVec<(fn() -> &'static TypeInfo, Schedule), 128>,
+    pub(crate) resources: ConstVec<InnerResource, 128>,
+    pub(crate) schedules: ConstVec<InnerSchedule, 128>,
+    pub(crate) capabilities: ConstVec<Capability, 32>,
+    /// Names of the features declared by the mod, see [`Mod::add_feature`]
+    pub(crate) features: ConstVec<&'static str, 32>,
+    /// The feature resources and systems are added to. Zero is the mod itself, every other
+    /// feature is one past its index in `features`
+    pub(crate) current_feature: usize,
+    pub(crate) dependencies: ConstVec<Dependency, 32>,
+}
+
+/// Another mod that must be loaded before this one can start, see [`Mod::add_dependency`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dependency {
+    /// The package name of the mod, or a uri it can be loaded from
+    pub id: &'static str,
+    /// The versions of the mod this one works with, as a semver requirement
+    pub version: &'static str,
+}
+
+/// A privilege the host must grant a mod before it can be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Spawn entities in the host's world
+    SpawnEntities,
+    /// Call a custom function registered by the host, by name. Mods import it from the
+    /// `bevy_harmonize_host` module
+    HostFunction(&'static str),
 }
 
 impl Schema {
     pub const fn new() -> Self {
         Self {
             name: None,
+            version: "0.0.0",
             types: ConstVec::new(),
             resources: ConstVec::new(),
             schedules: ConstVec::new(),
+            capabilities: ConstVec::new(),
+            features: ConstVec::new(),
+            current_feature: 0,
+            dependencies: ConstVec::new(),
         }
     }
 
@@ -32,6 +67,16 @@ impl Schema {
         self.name
     }
 
+    /// The semver version of the mod, see [`Mod::version`]
+    pub const fn version(&self) -> &'static str {
+        self.version
+    }
+
+    /// The version of this crate the mod is built against
+    pub const fn api_version(&self) -> &'static str {
+        crate::VERSION
+    }
+
     pub const fn types(&self) -> Types {
         Types {
             next: 0,
@@ -46,6 +91,22 @@ impl Schema {
         }
     }
 
+    /// Every capability required by the mod, in the order they were declared
+    pub const fn capabilities(&self) -> &[Capability] {
+        self.capabilities.into_slice()
+    }
+
+    /// Every mod this one depends on, in the order they were declared
+    pub const fn dependencies(&self) -> &[Dependency] {
+        self.dependencies.into_slice()
+    }
+
+    /// The names of the features declared by the mod. Resources and schedules refer to them
+    /// by one past their index, zero being the mod itself
+    pub const fn features(&self) -> &[&'static str] {
+        self.features.into_slice()
+    }
+
     pub const fn schedules(&self) -> Schedules {
         Schedules {
             next: 0,
@@ -94,17 +155,31 @@ impl<'a> Iterator for Types<'a> {
 
 pub struct Resources<'a> {
     next: usize,
-    getters: &'a [(fn() -> &'static TypeInfo, fn() -> Vec<u8>)],
+    getters: &'a [InnerResource],
 }
 
+pub(crate) type InnerResource = (
+    fn() -> &'static TypeInfo,
+    fn() -> Vec<u8>,
+    fn() -> Vec<(TypeId, Vec<FieldLayout>)>,
+    usize,
+);
+
 impl<'a> Iterator for Resources<'a> {
-    type Item = (&'static TypeInfo, Vec<u8>);
+    type Item = (
+        &'static TypeInfo,
+        Vec<u8>,
+        Vec<(TypeId, Vec<FieldLayout>)>,
+        usize,
+    );
 
     fn next(&mut self) -> Option<Self::Item> {
         let current = self
             .getters
             .get(self.next)
-            .map(|(getter1, getter2)| (getter1(), getter2()));
+            .map(|(getter1, getter2, getter3, feature)| {
+                (getter1(), getter2(), getter3(), *feature)
+            });
         self.next += 1;
         current
     }
@@ -112,17 +187,19 @@ impl<'a> Iterator for Resources<'a> {
 
 pub struct Schedules<'a> {
     next: usize,
-    getters: &'a [(fn() -> &'static TypeInfo, Schedule)],
+    getters: &'a [InnerSchedule],
 }
 
+pub(crate) type InnerSchedule = (fn() -> &'static TypeInfo, Schedule, usize);
+
 impl<'a> Iterator for Schedules<'a> {
-    type Item = (&'static TypeInfo, common::Schedule);
+    type Item = (&'static TypeInfo, common::Schedule, usize);
 
     fn next(&mut self) -> Option<Self::Item> {
         let current = self
             .getters
             .get(self.next)
-            .map(|(getter, schedule)| (getter(), schedule.build()));
+            .map(|(getter, schedule, feature)| (getter(), schedule.build(), *feature));
         self.next += 1;
         current
     }
@@ -1,7 +1,8 @@
 #![allow(non_local_definitions)] // TODO: Fix downstream in bart
 
 use anyhow::*;
-use common::{ModManifest, RawWasmVec};
+use common::{ModManifest, ModPackage, PackageSignature, RawWasmVec, StableId, TypeSignature};
+use ed25519_dalek::{Signer, SigningKey};
 use postprocess::{transform_wasm, TypeAddress};
 use sha2::{Digest, Sha256};
 use std::{path::PathBuf, time::Instant};
@@ -15,10 +16,14 @@ mod fs_utils;
 mod postprocess;
 mod templates;
 
+/// Builds every mod in the mods directory into a package
+///
+/// Packages are signed with the signing key when one is given
 pub async fn build(
     release: bool,
     mods_directory: PathBuf,
     cargo_directory: PathBuf,
+    signing_key: Option<SigningKey>,
 ) -> Result<Vec<PathBuf>> {
     let start = Instant::now();
     info!("Building mods from {:?}", mods_directory);
@@ -96,19 +101,34 @@ pub async fn build(
         cargo_build(&dir, packages, release).await?;
     }
 
-    // Delete old wasm files
-    fs_utils::empty_dir_conditional(&dir.dest, |path| path.ends_with(".wasm")).await?;
+    // Delete the outputs of earlier builds, including the loose wasm and manifest files
+    // written before mods were packaged, which the loader would still pick up
+    fs_utils::empty_dir_conditional(&dir.dest, |path| {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        [
+            ModPackage::EXTENSION,
+            ModSource::WASM,
+            ModSource::MANIFEST,
+            ModSource::WASM_DEBUG,
+            ModSource::MANIFEST_JSON,
+        ]
+        .iter()
+        .any(|extension| file_name.ends_with(&format!(".{}", extension)))
+    })
+    .await?;
 
-    // Move the generated wasm files to the build directory
-    let mut wasm_files = Vec::with_capacity(sources.len());
+    // Package the generated wasm files in the build directory
+    let mut packages = Vec::with_capacity(sources.len());
     for source in sources.iter_mut() {
-        wasm_files.push(source.finish(&dir).await?);
+        packages.push(source.finish(&dir, signing_key.as_ref()).await?);
     }
 
     let duration = start.elapsed();
-    info!("Successfully built mods {:?} in {:?}", wasm_files, duration);
+    info!("Successfully built mods {:?} in {:?}", packages, duration);
 
-    Ok(wasm_files)
+    Ok(packages)
 }
 
 struct Directories {
@@ -197,7 +217,7 @@ impl ModSource {
     const WASM: &str = "wasm";
     const WASM_DEBUG: &str = "wasm.wat";
     const MANIFEST: &str = "manifest";
-    const MANIFEST_DEBUG: &str = "manifest.txt";
+    const MANIFEST_JSON: &str = "manifest.json";
     const SOURCE: &str = "_source";
     const IMPORTS: &str = "_imports";
     const EXPORT_MANIFEST: &str = "_export_manifest";
@@ -275,7 +295,9 @@ impl ModSource {
         .await?;
         fs_utils::write_template(
             export_manifest_path.join("lib.rs"),
-            templates::ExportsManifestLib {},
+            templates::ExportsManifestLib {
+                crate_name: &format!("{}{}", name, Self::SOURCE).replace('-', "_"),
+            },
         )
         .await?;
 
@@ -291,26 +313,32 @@ impl ModSource {
 
         // Try loading manifest from the previous build
         if self.manifest.is_none() {
-            let manifest_path = dir.dest.join(&self.name).with_extension(Self::MANIFEST);
-            let manifest_bytes = fs_utils::read(&manifest_path).await?;
-            let (manifest, _) = bincode::decode_from_slice::<ModManifest, _>(
-                &manifest_bytes[..],
-                bincode::config::standard(),
-            )
-            .with_context(|| format!("Failed to read manifest file: {:?}", manifest_path))?;
+            let package_path = dir
+                .dest
+                .join(&self.name)
+                .with_extension(ModPackage::EXTENSION);
+            let package_bytes = fs_utils::read(&package_path).await?;
+            let package = ModPackage::decode(&package_bytes)
+                .with_context(|| format!("Failed to read package file: {:?}", package_path))?;
+            let manifest = ModManifest::decode(&package.manifest[..])
+                .with_context(|| format!("Failed to read manifest of: {:?}", package_path))?;
 
             self.manifest = Some(manifest);
         }
 
         let manifest = self.manifest.as_ref().unwrap();
 
-        let systems: Vec<_> = manifest
+        let paths: Vec<_> = manifest
             .systems()
+            .iter()
+            .map(|system| system_path(&system.name))
+            .collect();
+        let systems: Vec<_> = paths
             .iter()
             .enumerate()
-            .map(|(id, system)| templates::ExportsSystem {
+            .map(|(id, path)| templates::ExportsSystem {
                 id: id as u32,
-                name: &system.name,
+                name: path,
             })
             .collect();
 
@@ -335,7 +363,7 @@ impl ModSource {
         )
         .await?;
 
-        self.types = TypeAddress::from_type_signatures(manifest.types.clone().into_iter());
+        self.types = TypeAddress::from_type_signatures(manifest.types.clone().into_iter())?;
         let components: Vec<_> = self
             .types
             .iter()
@@ -427,10 +455,8 @@ impl ModSource {
             bail!("Manifest bytes are empty");
         }
 
-        let (manifest, _) = bincode::decode_from_slice::<ModManifest, _>(
-            manifest_bytes,
-            bincode::config::standard(),
-        )?;
+        let manifest = ModManifest::decode(manifest_bytes)?;
+        check_resources(&manifest)?;
 
         if self.manifest.as_ref() != Some(&manifest) {
             if self.manifest.is_some() {
@@ -445,33 +471,104 @@ impl ModSource {
         Ok(())
     }
 
-    async fn finish(&mut self, dir: &Directories) -> Result<PathBuf> {
+    async fn finish(
+        &mut self,
+        dir: &Directories,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<PathBuf> {
         let package_name = self.get_systems_export_package();
         let src = dir.wasm_dest.join(package_name).with_extension(Self::WASM);
-        let dest = dir.dest.join(&self.name).with_extension(Self::WASM);
+        let dest = dir
+            .dest
+            .join(&self.name)
+            .with_extension(ModPackage::EXTENSION);
 
         let bytes = transform_wasm(&src, &self.types).await?;
 
-        fs_utils::write(&dest, &bytes).await?;
-
         let printed = wasmprinter::print_bytes(&bytes)?;
         fs_utils::write(dest.with_extension(Self::WASM_DEBUG), printed).await?;
 
         let mut manifest = self.manifest.take().expect("Manifest should be loaded");
         manifest.wasm_hash = common::FileHash::from_sha256(Sha256::digest(&bytes).into());
 
-        let as_string = format!("{:#?}", manifest);
-        let path = dest.with_extension(Self::MANIFEST_DEBUG);
-        fs_utils::write(&path, as_string).await?;
+        let path = dest.with_extension(Self::MANIFEST_JSON);
+        fs_utils::write(&path, manifest.to_json()?).await?;
+
+        let encoded_manifest = manifest.encode()?;
 
-        let encoded_manifest = bincode::encode_to_vec(manifest, bincode::config::standard())?;
-        let path = dest.with_extension(Self::MANIFEST);
-        fs_utils::write(&path, encoded_manifest).await?;
+        let mut package = ModPackage::new(encoded_manifest, bytes);
+        package.metadata.push((
+            "built_with".to_owned(),
+            format!("bevy_harmonize_build {}", env!("CARGO_PKG_VERSION")),
+        ));
+        if let Some(signing_key) = signing_key {
+            sign_package(&mut package, signing_key)?;
+        }
+        fs_utils::write(&dest, package.encode()?).await?;
 
         Ok(dest)
     }
 }
 
+/// Fails if a resource holds a type the host can't lay out in memory
+///
+/// The host lays out resources from their type signatures, which only describe the memory of
+/// structs, tuples, arrays and primitives
+fn check_resources(manifest: &ModManifest) -> Result<()> {
+    let resources = manifest
+        .features
+        .iter()
+        .flat_map(|feature| feature.resources.iter());
+    for (resource, _) in resources {
+        check_resource_type(&manifest.types, resource)
+            .with_context(|| format!("Resource {} can't be stored in memory", resource))?;
+    }
+    Ok(())
+}
+
+fn check_resource_type(types: &[TypeSignature], id: &StableId) -> Result<()> {
+    // Primitives may have no signature of their own
+    let Some(signature) = types.iter().find(|ty| ty.stable_id() == *id) else {
+        return Ok(());
+    };
+    match signature {
+        TypeSignature::Struct { fields, .. } => fields
+            .iter()
+            .try_for_each(|field| check_resource_type(types, &field.ty)),
+        TypeSignature::TupleStruct { fields, .. } | TypeSignature::Tuple { fields, .. } => fields
+            .iter()
+            .try_for_each(|field| check_resource_type(types, field)),
+        TypeSignature::Array { item_ty, .. } => check_resource_type(types, item_ty),
+        // Primitives are opaque to reflection
+        TypeSignature::Opaque { ty, .. } if ty.crate_name == "unknown" => Ok(()),
+        _ => bail!(
+            "{} is not a struct, tuple, array or primitive, which resources are limited to",
+            id
+        ),
+    }
+}
+
+/// Turns the name of a system into a path the export crate can call it by
+///
+/// Generic systems such as `resource_changed<T>` need a turbofish, and the api is only known
+/// to the export crate as `api`
+fn system_path(name: &str) -> String {
+    let name = name.replace('<', "::<");
+    match name.strip_prefix("bevy_harmonize_api::") {
+        Some(path) => format!("api::{}", path),
+        None => name,
+    }
+}
+
+fn sign_package(package: &mut ModPackage, signing_key: &SigningKey) -> Result<()> {
+    let signature = signing_key.sign(&package.signed_bytes()?);
+    package.signature = Some(PackageSignature {
+        public_key: signing_key.verifying_key().to_bytes(),
+        signature: signature.to_bytes(),
+    });
+    Ok(())
+}
+
 async fn cargo_build(dir: &Directories, packages: Vec<String>, release: bool) -> Result<()> {
     let mut command = CargoCommand::new("build")?;
     command
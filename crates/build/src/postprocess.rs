@@ -3,7 +3,6 @@ use std::{
     io::{BufReader, Seek},
     ops::Range,
     path::Path,
-    u32,
 };
 
 use anyhow::*;
@@ -28,38 +27,18 @@ pub struct TypeAddress {
 }
 
 impl TypeAddress {
-    pub fn from_type_signatures(types: impl Iterator<Item = TypeSignature>) -> Vec<TypeAddress> {
-        let mut address: u32 = u32::MAX;
-        types
+    pub fn from_type_signatures(
+        types: impl Iterator<Item = TypeSignature>,
+    ) -> Result<Vec<TypeAddress>> {
+        let types: Vec<_> = types.collect();
+        let addresses = common::type_addresses(&types)?
             .into_iter()
-            // Take all types with known size and alignment
-            .filter_map(|ty| match (ty.size(), ty.align()) {
-                (Some(size), Some(align)) => Some((ty, size as u32, align as u32)),
-                _ => None,
+            .map(|(signature, address)| TypeAddress {
+                signature: signature.clone(),
+                address,
             })
-            // Size should be a multiple of the alignment
-            // Alignment must be a power of 2, greater than 0, less than or equal to 128
-            // Size must be greater than 0
-            .filter(|(_, size, align)| {
-                *size > 0
-                    && *align > 0
-                    && size % align == 0
-                    && align.is_power_of_two()
-                    && *align <= 128
-            })
-            // Give them each their own unique address range
-            .map(move |(ty, size, align)| {
-                // Ensure it doesn't overlap with the previous type
-                address -= size;
-                // Ensure the address is aligned to the next multiple of the alignment
-                address -= address % align;
-
-                TypeAddress {
-                    signature: ty,
-                    address: address..address + size,
-                }
-            })
-            .collect()
+            .collect();
+        Ok(addresses)
     }
 }
 
@@ -179,6 +158,7 @@ mod tests {
             align,
             generics: vec![],
             fields: vec![],
+            layout: vec![],
         }
     }
 
@@ -205,7 +185,7 @@ mod tests {
             new(&invalid, Some(256), Some(256)),
         ];
 
-        let addresses = TypeAddress::from_type_signatures(types.into_iter());
+        let addresses = TypeAddress::from_type_signatures(types.into_iter()).unwrap();
         assert_eq!(addresses.len(), 4);
 
         let lower = u32::MAX - 127 - 256;
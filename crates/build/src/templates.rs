@@ -42,7 +42,10 @@ pub struct ExportsManifestCargo<'a> {
 
 #[derive(bart_derive::BartDisplay)]
 #[template = "templates/export/manifest/lib.rs.template"]
-pub struct ExportsManifestLib {}
+pub struct ExportsManifestLib<'a> {
+    /// The name of the source crate, as seen by `bevy_reflect`
+    pub crate_name: &'a str,
+}
 
 #[derive(bart_derive::BartDisplay)]
 #[template = "templates/export/systems/Cargo.toml.template"]
@@ -0,0 +1,62 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::*;
+use clap::{Parser, Subcommand};
+use common::{ManifestDiff, ModManifest, ModPackage};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compares two versions of a mod, and exits with an error if the new one has breaking changes
+    ///
+    /// Each version is a ".hmod" package, a ".json" manifest or a ".manifest" file
+    Diff { old: PathBuf, new: PathBuf },
+}
+
+fn main() -> Result<ExitCode> {
+    match Cli::parse().command {
+        Command::Diff { old, new } => {
+            let diff = ManifestDiff::new(&read_manifest(&old)?, &read_manifest(&new)?);
+            if diff.changes.is_empty() {
+                println!("No changes");
+            } else {
+                print!("{}", diff);
+            }
+
+            if diff.is_breaking() {
+                return Ok(ExitCode::FAILURE);
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<ModManifest> {
+    let bytes = std::fs::read(path).map_err(|err| anyhow!("Failed to read {:?}: {}", path, err))?;
+
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    if extension == Some(ModPackage::EXTENSION) {
+        let package = ModPackage::decode(&bytes)
+            .map_err(|err| anyhow!("Failed to parse package {:?}: {}", path, err))?;
+        return ModManifest::decode(&package.manifest)
+            .map_err(|err| anyhow!("Failed to parse manifest of {:?}: {}", path, err));
+    }
+    if extension == Some("json") {
+        let json = String::from_utf8(bytes)
+            .map_err(|err| anyhow!("Failed to read {:?}: {}", path, err))?;
+        return ModManifest::from_json(&json)
+            .map_err(|err| anyhow!("Failed to parse manifest {:?}: {}", path, err));
+    }
+
+    ModManifest::decode(&bytes)
+        .map_err(|err| anyhow!("Failed to parse manifest {:?}: {}", path, err))
+}
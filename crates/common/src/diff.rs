@@ -0,0 +1,909 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, mem::discriminant};
+
+use crate::{
+    Capability, Constraint, Dependency, ModManifest, StableId, SystemId, SystemSet, TypeSignature,
+    VariantSignature,
+};
+
+/// Whether a new version of a mod can take the place of the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Compatibility {
+    /// The state of the previous version carries over, and nothing relying on it breaks
+    Compatible,
+    /// Some state is reset or lost, or something relying on the previous version may break
+    Breaking,
+}
+
+/// A single difference between two manifests
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    TypeAdded(StableId),
+    TypeRemoved(StableId),
+    /// A type changed kind, such as a struct turning into an enum
+    KindChanged(StableId),
+    CrateVersionChanged {
+        ty: StableId,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// Fields of tuples and tuple structs are named by their index
+    FieldAdded {
+        ty: StableId,
+        field: String,
+    },
+    FieldRemoved {
+        ty: StableId,
+        field: String,
+    },
+    FieldTypeChanged {
+        ty: StableId,
+        field: String,
+        old: StableId,
+        new: StableId,
+    },
+    SizeChanged {
+        ty: StableId,
+        old: Option<usize>,
+        new: Option<usize>,
+    },
+    AlignChanged {
+        ty: StableId,
+        old: Option<usize>,
+        new: Option<usize>,
+    },
+    /// A variant was added. Enums that changed aren't migrated, so their values are reset
+    VariantAdded {
+        ty: StableId,
+        variant: String,
+    },
+    VariantRemoved {
+        ty: StableId,
+        variant: String,
+    },
+    /// A variant changed kind, or its fields changed
+    VariantChanged {
+        ty: StableId,
+        variant: String,
+    },
+    /// The items, keys or values of a collection changed type
+    ItemTypeChanged {
+        ty: StableId,
+        old: StableId,
+        new: StableId,
+    },
+    CapacityChanged {
+        ty: StableId,
+        old: usize,
+        new: usize,
+    },
+    ResourceAdded(StableId),
+    ResourceRemoved(StableId),
+    /// Systems are named by their path
+    SystemAdded(String),
+    SystemRemoved(String),
+    SystemParamsChanged(String),
+    /// Constraints are described by the systems and sets they apply to
+    ConstraintAdded(String),
+    ConstraintRemoved(String),
+    CapabilityAdded(Capability),
+    CapabilityRemoved(Capability),
+    DependencyAdded(Dependency),
+    DependencyRemoved(Dependency),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TypeAdded(ty) => write!(f, "Type {} was added", ty),
+            Self::TypeRemoved(ty) => write!(f, "Type {} was removed", ty),
+            Self::KindChanged(ty) => write!(f, "Type {} changed kind", ty),
+            Self::CrateVersionChanged { ty, old, new } => write!(
+                f,
+                "Crate of type {} changed version from {} to {}",
+                ty,
+                or_unknown(old),
+                or_unknown(new)
+            ),
+            Self::FieldAdded { ty, field } => write!(f, "Field {}.{} was added", ty, field),
+            Self::FieldRemoved { ty, field } => write!(f, "Field {}.{} was removed", ty, field),
+            Self::FieldTypeChanged {
+                ty,
+                field,
+                old,
+                new,
+            } => write!(
+                f,
+                "Field {}.{} changed type from {} to {}",
+                ty, field, old, new
+            ),
+            Self::SizeChanged { ty, old, new } => write!(
+                f,
+                "Type {} changed size from {} to {}",
+                ty,
+                or_unknown(old),
+                or_unknown(new)
+            ),
+            Self::AlignChanged { ty, old, new } => write!(
+                f,
+                "Type {} changed alignment from {} to {}",
+                ty,
+                or_unknown(old),
+                or_unknown(new)
+            ),
+            Self::VariantAdded { ty, variant } => {
+                write!(f, "Variant {}::{} was added", ty, variant)
+            }
+            Self::VariantRemoved { ty, variant } => {
+                write!(f, "Variant {}::{} was removed", ty, variant)
+            }
+            Self::VariantChanged { ty, variant } => {
+                write!(f, "Variant {}::{} changed", ty, variant)
+            }
+            Self::ItemTypeChanged { ty, old, new } => {
+                write!(f, "Items of {} changed type from {} to {}", ty, old, new)
+            }
+            Self::CapacityChanged { ty, old, new } => {
+                write!(f, "Type {} changed capacity from {} to {}", ty, old, new)
+            }
+            Self::ResourceAdded(ty) => write!(f, "Resource {} was added", ty),
+            Self::ResourceRemoved(ty) => write!(f, "Resource {} was removed", ty),
+            Self::SystemAdded(name) => write!(f, "System {} was added", name),
+            Self::SystemRemoved(name) => write!(f, "System {} was removed", name),
+            Self::SystemParamsChanged(name) => write!(f, "Params of system {} changed", name),
+            Self::ConstraintAdded(constraint) => write!(f, "Constraint {} was added", constraint),
+            Self::ConstraintRemoved(constraint) => {
+                write!(f, "Constraint {} was removed", constraint)
+            }
+            Self::CapabilityAdded(capability) => {
+                write!(f, "Capability {} is now required", capability)
+            }
+            Self::CapabilityRemoved(capability) => {
+                write!(f, "Capability {} is no longer required", capability)
+            }
+            Self::DependencyAdded(dependency) => write!(
+                f,
+                "Dependency on {} {} was added",
+                dependency.id, dependency.version
+            ),
+            Self::DependencyRemoved(dependency) => write!(
+                f,
+                "Dependency on {} {} was removed",
+                dependency.id, dependency.version
+            ),
+        }
+    }
+}
+
+/// A change between two manifests, and whether it breaks anything
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestChange {
+    pub change: Change,
+    pub compatibility: Compatibility,
+}
+
+impl fmt::Display for ManifestChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.compatibility {
+            Compatibility::Compatible => write!(f, "compatible: {}", self.change),
+            Compatibility::Breaking => write!(f, "breaking: {}", self.change),
+        }
+    }
+}
+
+/// The differences between two versions of a mod's manifest
+///
+/// Changes are classified by whether what relied on the previous version still holds. Resource
+/// values are carried over field by field on reload, so adding fields, variants, resources or
+/// systems is compatible, while removing them or changing their type is breaking. Requiring
+/// more of the host, like a new capability, dependency or ordering constraint, is breaking too
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ManifestDiff {
+    pub changes: Vec<ManifestChange>,
+}
+
+impl ManifestDiff {
+    pub fn new(old: &ModManifest, new: &ModManifest) -> Self {
+        let mut diff = Self::default();
+        diff.types(old, new);
+        diff.resources(old, new);
+        diff.systems(old, new);
+        diff.constraints(old, new);
+        diff.sets(&old.capabilities, &new.capabilities, |capability, added| {
+            if added {
+                (Change::CapabilityAdded(capability), Compatibility::Breaking)
+            } else {
+                (
+                    Change::CapabilityRemoved(capability),
+                    Compatibility::Compatible,
+                )
+            }
+        });
+        diff.sets(&old.dependencies, &new.dependencies, |dependency, added| {
+            if added {
+                (Change::DependencyAdded(dependency), Compatibility::Breaking)
+            } else {
+                (
+                    Change::DependencyRemoved(dependency),
+                    Compatibility::Compatible,
+                )
+            }
+        });
+        diff
+    }
+
+    /// The compatibility of the new version as a whole
+    pub fn compatibility(&self) -> Compatibility {
+        self.changes
+            .iter()
+            .map(|change| change.compatibility)
+            .max()
+            .unwrap_or(Compatibility::Compatible)
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.compatibility() == Compatibility::Breaking
+    }
+
+    pub fn breaking(&self) -> impl Iterator<Item = &ManifestChange> {
+        self.changes
+            .iter()
+            .filter(|change| change.compatibility == Compatibility::Breaking)
+    }
+
+    fn push(&mut self, change: Change, compatibility: Compatibility) {
+        self.changes.push(ManifestChange {
+            change,
+            compatibility,
+        });
+    }
+
+    fn breaking_change(&mut self, change: Change) {
+        self.push(change, Compatibility::Breaking);
+    }
+
+    fn compatible_change(&mut self, change: Change) {
+        self.push(change, Compatibility::Compatible);
+    }
+
+    /// Adds a change for every value only found in one of the lists
+    fn sets<T: Clone + PartialEq>(
+        &mut self,
+        old: &[T],
+        new: &[T],
+        change: impl Fn(T, bool) -> (Change, Compatibility),
+    ) {
+        for value in old.iter().filter(|value| !new.contains(value)) {
+            let (change, compatibility) = change(value.clone(), false);
+            self.push(change, compatibility);
+        }
+        for value in new.iter().filter(|value| !old.contains(value)) {
+            let (change, compatibility) = change(value.clone(), true);
+            self.push(change, compatibility);
+        }
+    }
+
+    fn types(&mut self, old: &ModManifest, new: &ModManifest) {
+        for old_type in old.types.iter() {
+            let id = old_type.stable_id();
+            match new.types.iter().find(|ty| ty.stable_id() == id) {
+                Some(new_type) => self.signature(old_type, new_type),
+                None => self.breaking_change(Change::TypeRemoved(id)),
+            }
+        }
+        for new_type in new.types.iter() {
+            let id = new_type.stable_id();
+            if !old.types.iter().any(|ty| ty.stable_id() == id) {
+                self.compatible_change(Change::TypeAdded(id));
+            }
+        }
+    }
+
+    fn signature(&mut self, old: &TypeSignature, new: &TypeSignature) {
+        let (old_id, ty) = (old.stable_id(), new.stable_id());
+        if discriminant(old) != discriminant(new) {
+            self.breaking_change(Change::KindChanged(ty));
+            return;
+        }
+
+        if old_id.crate_version != ty.crate_version {
+            // Values migrate whatever the version, but mods using the type may break
+            let compatibility = if old_id.is_compatible(&ty) {
+                Compatibility::Compatible
+            } else {
+                Compatibility::Breaking
+            };
+            let change = Change::CrateVersionChanged {
+                ty: ty.clone(),
+                old: old_id.crate_version.clone(),
+                new: ty.crate_version.clone(),
+            };
+            self.push(change, compatibility);
+        }
+
+        // Values with fields are migrated field by field, so they may change layout
+        let fields = (named_fields(old), named_fields(new));
+        let layout = if fields.0.is_some() {
+            Compatibility::Compatible
+        } else {
+            Compatibility::Breaking
+        };
+        if old.size() != new.size() {
+            let change = Change::SizeChanged {
+                ty: ty.clone(),
+                old: old.size(),
+                new: new.size(),
+            };
+            self.push(change, layout);
+        }
+        if old.align() != new.align() {
+            let change = Change::AlignChanged {
+                ty: ty.clone(),
+                old: old.align(),
+                new: new.align(),
+            };
+            self.push(change, layout);
+        }
+
+        if let (Some(old_fields), Some(new_fields)) = fields {
+            for (field, old_ty) in old_fields.iter() {
+                match new_fields.iter().find(|(name, _)| name == field) {
+                    Some((_, new_ty)) if new_ty != old_ty => {
+                        self.breaking_change(Change::FieldTypeChanged {
+                            ty: ty.clone(),
+                            field: field.clone(),
+                            old: old_ty.clone(),
+                            new: new_ty.clone(),
+                        })
+                    }
+                    Some(_) => {}
+                    None => self.breaking_change(Change::FieldRemoved {
+                        ty: ty.clone(),
+                        field: field.clone(),
+                    }),
+                }
+            }
+            for (field, _) in new_fields.iter() {
+                if !old_fields.iter().any(|(name, _)| name == field) {
+                    self.compatible_change(Change::FieldAdded {
+                        ty: ty.clone(),
+                        field: field.clone(),
+                    });
+                }
+            }
+            return;
+        }
+
+        match (old, new) {
+            (
+                TypeSignature::Enum {
+                    variants: old_variants,
+                    ..
+                },
+                TypeSignature::Enum { variants, .. },
+            ) => self.variants(&ty, old_variants, variants),
+            (
+                TypeSignature::Array {
+                    item_ty: old_item,
+                    capacity: old_capacity,
+                    ..
+                },
+                TypeSignature::Array {
+                    item_ty, capacity, ..
+                },
+            ) => {
+                self.item_type(&ty, old_item, item_ty);
+                if old_capacity != capacity {
+                    // Items past the new capacity are dropped
+                    let compatibility = if capacity > old_capacity {
+                        Compatibility::Compatible
+                    } else {
+                        Compatibility::Breaking
+                    };
+                    let change = Change::CapacityChanged {
+                        ty: ty.clone(),
+                        old: *old_capacity,
+                        new: *capacity,
+                    };
+                    self.push(change, compatibility);
+                }
+            }
+            (
+                TypeSignature::List {
+                    item_ty: old_item, ..
+                },
+                TypeSignature::List { item_ty, .. },
+            )
+            | (
+                TypeSignature::Set {
+                    value_ty: old_item, ..
+                },
+                TypeSignature::Set {
+                    value_ty: item_ty, ..
+                },
+            ) => self.item_type(&ty, old_item, item_ty),
+            (
+                TypeSignature::Map {
+                    key_ty: old_key,
+                    value_ty: old_value,
+                    ..
+                },
+                TypeSignature::Map {
+                    key_ty, value_ty, ..
+                },
+            ) => {
+                self.item_type(&ty, old_key, key_ty);
+                self.item_type(&ty, old_value, value_ty);
+            }
+            _ => {}
+        }
+    }
+
+    fn item_type(&mut self, ty: &StableId, old: &StableId, new: &StableId) {
+        if old != new {
+            self.breaking_change(Change::ItemTypeChanged {
+                ty: ty.clone(),
+                old: old.clone(),
+                new: new.clone(),
+            });
+        }
+    }
+
+    fn variants(&mut self, ty: &StableId, old: &[VariantSignature], new: &[VariantSignature]) {
+        for old_variant in old.iter() {
+            let variant = variant_name(old_variant).to_string();
+            match new.iter().find(|other| variant_name(other) == variant) {
+                Some(new_variant) if new_variant != old_variant => {
+                    self.breaking_change(Change::VariantChanged {
+                        ty: ty.clone(),
+                        variant,
+                    })
+                }
+                Some(_) => {}
+                None => self.breaking_change(Change::VariantRemoved {
+                    ty: ty.clone(),
+                    variant,
+                }),
+            }
+        }
+        for new_variant in new.iter() {
+            let variant = variant_name(new_variant);
+            if !old.iter().any(|other| variant_name(other) == variant) {
+                self.breaking_change(Change::VariantAdded {
+                    ty: ty.clone(),
+                    variant: variant.to_string(),
+                });
+            }
+        }
+    }
+
+    fn resources(&mut self, old: &ModManifest, new: &ModManifest) {
+        let resources = |manifest: &ModManifest| -> Vec<StableId> {
+            manifest
+                .features
+                .iter()
+                .flat_map(|feature| feature.resources.iter().map(|(id, _)| id.clone()))
+                .collect()
+        };
+        self.sets(&resources(old), &resources(new), |id, added| {
+            if added {
+                (Change::ResourceAdded(id), Compatibility::Compatible)
+            } else {
+                (Change::ResourceRemoved(id), Compatibility::Breaking)
+            }
+        });
+    }
+
+    /// Systems are matched by name, since their ids aren't stable across builds
+    fn systems(&mut self, old: &ModManifest, new: &ModManifest) {
+        let new_systems = new.systems();
+        for old_system in old.systems() {
+            match new_systems
+                .iter()
+                .find(|system| system.name == old_system.name)
+            {
+                Some(new_system) if new_system.params != old_system.params => {
+                    self.compatible_change(Change::SystemParamsChanged(old_system.name.clone()))
+                }
+                Some(_) => {}
+                None => self.breaking_change(Change::SystemRemoved(old_system.name.clone())),
+            }
+        }
+        let old_systems = old.systems();
+        for new_system in new_systems {
+            if !old_systems
+                .iter()
+                .any(|system| system.name == new_system.name)
+            {
+                self.compatible_change(Change::SystemAdded(new_system.name.clone()));
+            }
+        }
+    }
+
+    fn constraints(&mut self, old: &ModManifest, new: &ModManifest) {
+        let (old, new) = (constraints(old), constraints(new));
+        for constraint in old.difference(&new) {
+            self.compatible_change(Change::ConstraintRemoved(constraint.clone()));
+        }
+        for constraint in new.difference(&old) {
+            self.breaking_change(Change::ConstraintAdded(constraint.clone()));
+        }
+    }
+}
+
+impl fmt::Display for ManifestDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// The fields of types laid out field by field, named by their index if they have no name
+fn named_fields(signature: &TypeSignature) -> Option<Vec<(String, StableId)>> {
+    match signature {
+        TypeSignature::Struct { fields, .. } => Some(
+            fields
+                .iter()
+                .map(|field| (field.name.clone(), field.ty.clone()))
+                .collect(),
+        ),
+        TypeSignature::TupleStruct { fields, .. } | TypeSignature::Tuple { fields, .. } => Some(
+            fields
+                .iter()
+                .enumerate()
+                .map(|(index, ty)| (index.to_string(), ty.clone()))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn or_unknown<T: fmt::Display>(value: &Option<T>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+fn variant_name(variant: &VariantSignature) -> &str {
+    match variant {
+        VariantSignature::Struct { name, .. }
+        | VariantSignature::Tuple { name, .. }
+        | VariantSignature::Unit { name } => name,
+    }
+}
+
+/// Describes every constraint of a manifest, with systems named by their path
+fn constraints(manifest: &ModManifest) -> BTreeSet<String> {
+    let names: BTreeMap<SystemId, &str> = manifest
+        .systems()
+        .into_iter()
+        .map(|system| (system.id, system.name.as_str()))
+        .collect();
+    let system = |id: &SystemId| match names.get(id) {
+        Some(name) => (*name).to_string(),
+        None => format!("{:?}", id),
+    };
+    let set = |set: &SystemSet| match set {
+        SystemSet::Anonymous(systems) => {
+            let systems: Vec<_> = systems.iter().map(system).collect();
+            format!("({})", systems.join(", "))
+        }
+        SystemSet::Named(id) => id.to_string(),
+    };
+
+    let mut descriptions = BTreeSet::new();
+    for feature in manifest.features.iter() {
+        for descriptor in feature.schedules.iter() {
+            for constraint in descriptor.schedule.constraints.iter() {
+                let constraint = match constraint {
+                    Constraint::Order { before, after } => {
+                        format!("{} before {}", set(before), set(after))
+                    }
+                    Constraint::Condition {
+                        set: gated,
+                        condition,
+                    } => {
+                        format!("{} if {}", set(gated), system(condition))
+                    }
+                    Constraint::Includes {
+                        parent_name,
+                        set: child,
+                    } => {
+                        format!("{} in {}", set(child), parent_name)
+                    }
+                };
+                descriptions.insert(format!("{}: {}", descriptor.id, constraint));
+            }
+        }
+    }
+    descriptions
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::ToOwned, vec};
+
+    use super::*;
+    use crate::{
+        FeatureDescriptor, FieldLayout, FieldSignature, FileHash, Param, Schedule,
+        ScheduleDescriptor, System,
+    };
+
+    fn score(fields: Vec<(&str, &str)>, size: usize) -> TypeSignature {
+        TypeSignature::Struct {
+            ty: StableId::new("my_mod", "Score"),
+            size: Some(size),
+            align: Some(4),
+            generics: Vec::new(),
+            fields: fields
+                .iter()
+                .map(|(name, ty)| FieldSignature {
+                    name: (*name).to_owned(),
+                    ty: StableId::new("core", ty),
+                })
+                .collect(),
+            layout: fields
+                .iter()
+                .enumerate()
+                .map(|(index, _)| FieldLayout {
+                    offset: index * 4,
+                    size: 4,
+                })
+                .collect(),
+        }
+    }
+
+    fn system(name: &str, params: Vec<Param>) -> System {
+        System {
+            id: SystemId(name.len() as u64),
+            name: name.to_owned(),
+            params,
+        }
+    }
+
+    fn manifest(types: Vec<TypeSignature>, systems: Vec<System>) -> ModManifest {
+        let constraints = match systems.as_slice() {
+            [first, second, ..] => vec![Constraint::Order {
+                before: SystemSet::Anonymous(vec![first.id]),
+                after: SystemSet::Anonymous(vec![second.id]),
+            }],
+            _ => Vec::new(),
+        };
+        ModManifest {
+            wasm_hash: FileHash::empty(),
+            version: "1.0.0".to_owned(),
+            api_version: crate::VERSION.to_owned(),
+            common_version: crate::VERSION.to_owned(),
+            types,
+            features: vec![FeatureDescriptor {
+                name: "my_mod".to_owned(),
+                resources: vec![(StableId::new("my_mod", "Score"), vec![0; 4])],
+                schedules: vec![ScheduleDescriptor {
+                    id: StableId::new("bevy_harmonize_common", "Update"),
+                    schedule: Schedule {
+                        systems,
+                        constraints,
+                    },
+                }],
+            }],
+            capabilities: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn identical_manifests_have_no_changes() {
+        let manifest = manifest(vec![score(vec![("value", "u32")], 4)], Vec::new());
+        let diff = ManifestDiff::new(&manifest, &manifest);
+        assert!(diff.changes.is_empty());
+        assert_eq!(diff.compatibility(), Compatibility::Compatible);
+    }
+
+    #[test]
+    fn added_fields_are_compatible() {
+        let old = manifest(vec![score(vec![("value", "u32")], 4)], Vec::new());
+        let new = manifest(
+            vec![score(vec![("value", "u32"), ("best", "u32")], 8)],
+            Vec::new(),
+        );
+
+        let diff = ManifestDiff::new(&old, &new);
+        assert!(!diff.is_breaking(), "{}", diff);
+        assert!(diff.changes.iter().any(|change| change.change
+            == Change::FieldAdded {
+                ty: StableId::new("my_mod", "Score"),
+                field: "best".to_owned(),
+            }));
+        assert!(diff
+            .changes
+            .iter()
+            .any(|change| matches!(change.change, Change::SizeChanged { .. })));
+    }
+
+    #[test]
+    fn removed_and_retyped_fields_are_breaking() {
+        let old = manifest(
+            vec![score(vec![("value", "u32"), ("best", "u32")], 8)],
+            Vec::new(),
+        );
+        let new = manifest(vec![score(vec![("value", "f32")], 4)], Vec::new());
+
+        let diff = ManifestDiff::new(&old, &new);
+        let breaking: Vec<_> = diff.breaking().map(|change| &change.change).collect();
+        assert_eq!(
+            breaking,
+            [
+                &Change::FieldTypeChanged {
+                    ty: StableId::new("my_mod", "Score"),
+                    field: "value".to_owned(),
+                    old: StableId::new("core", "u32"),
+                    new: StableId::new("core", "f32"),
+                },
+                &Change::FieldRemoved {
+                    ty: StableId::new("my_mod", "Score"),
+                    field: "best".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn enum_variants_and_opaque_layouts() {
+        let enum_signature = |variants: Vec<VariantSignature>| TypeSignature::Enum {
+            ty: StableId::new("my_mod", "State"),
+            size: Some(1),
+            align: Some(1),
+            generics: Vec::new(),
+            variants,
+        };
+        let unit = |name: &str| VariantSignature::Unit {
+            name: name.to_owned(),
+        };
+        let opaque = |size| TypeSignature::Opaque {
+            ty: StableId::new("my_mod", "Handle"),
+            size: Some(size),
+            align: Some(size),
+            generics: Vec::new(),
+        };
+
+        let old = manifest(
+            vec![
+                enum_signature(vec![unit("Idle"), unit("Running")]),
+                opaque(4),
+            ],
+            Vec::new(),
+        );
+        let added = manifest(
+            vec![
+                enum_signature(vec![unit("Idle"), unit("Running"), unit("Paused")]),
+                opaque(4),
+            ],
+            Vec::new(),
+        );
+        let diff = ManifestDiff::new(&old, &added);
+        assert_eq!(
+            diff.changes,
+            [ManifestChange {
+                change: Change::VariantAdded {
+                    ty: StableId::new("my_mod", "State"),
+                    variant: "Paused".to_owned(),
+                },
+                compatibility: Compatibility::Breaking,
+            }]
+        );
+        assert!(!ManifestDiff::new(&old, &old).is_breaking());
+
+        // Unchanged enums carry over whatever the version of their crate
+        let versioned = |version: &str| {
+            let mut signature = enum_signature(vec![unit("Idle")]);
+            signature.stable_id_mut().crate_version = Some(version.to_owned());
+            manifest(vec![signature], Vec::new())
+        };
+        assert!(!ManifestDiff::new(&versioned("1.0.0"), &versioned("1.1.0")).is_breaking());
+
+        let removed = manifest(
+            vec![enum_signature(vec![unit("Idle")]), opaque(4)],
+            Vec::new(),
+        );
+        assert!(ManifestDiff::new(&old, &removed).is_breaking());
+
+        let resized = manifest(
+            vec![
+                enum_signature(vec![unit("Idle"), unit("Running")]),
+                opaque(8),
+            ],
+            Vec::new(),
+        );
+        let diff = ManifestDiff::new(&old, &resized);
+        assert_eq!(diff.breaking().count(), 2, "{}", diff);
+    }
+
+    #[test]
+    fn systems_and_constraints() {
+        let types = vec![score(vec![("value", "u32")], 4)];
+        let old = manifest(
+            types.clone(),
+            vec![
+                system("my_mod::a", Vec::new()),
+                system("my_mod::bb", Vec::new()),
+            ],
+        );
+
+        let params = vec![Param::Res {
+            mutable: false,
+            id: StableId::new("my_mod", "Score"),
+        }];
+        let new = manifest(
+            types.clone(),
+            vec![
+                system("my_mod::a", params),
+                system("my_mod::bb", Vec::new()),
+            ],
+        );
+        let diff = ManifestDiff::new(&old, &new);
+        assert_eq!(
+            diff.changes,
+            [ManifestChange {
+                change: Change::SystemParamsChanged("my_mod::a".to_owned()),
+                compatibility: Compatibility::Compatible,
+            }]
+        );
+
+        // Reordering the systems adds a constraint
+        let new = manifest(
+            types,
+            vec![
+                system("my_mod::bb", Vec::new()),
+                system("my_mod::a", Vec::new()),
+            ],
+        );
+        let diff = ManifestDiff::new(&old, &new);
+        assert!(diff.changes.contains(&ManifestChange {
+            change: Change::ConstraintAdded(
+                "bevy_harmonize_common::Update: (my_mod::bb) before (my_mod::a)".to_owned()
+            ),
+            compatibility: Compatibility::Breaking,
+        }));
+
+        let new = manifest(Vec::new(), Vec::new());
+        let diff = ManifestDiff::new(&old, &new);
+        assert!(diff
+            .changes
+            .iter()
+            .any(|change| change.change == Change::SystemRemoved("my_mod::bb".to_owned())));
+        assert!(diff
+            .changes
+            .iter()
+            .any(|change| change.change == Change::TypeRemoved(StableId::new("my_mod", "Score"))));
+    }
+
+    #[test]
+    fn crate_versions_and_requirements() {
+        let versioned = |version: &str| {
+            let mut signature = score(vec![("value", "u32")], 4);
+            *signature.stable_id_mut() = StableId::new("my_mod", "Score").with_version(version);
+            manifest(vec![signature], Vec::new())
+        };
+        assert!(!ManifestDiff::new(&versioned("1.0.0"), &versioned("1.2.0")).is_breaking());
+        assert!(ManifestDiff::new(&versioned("1.0.0"), &versioned("2.0.0")).is_breaking());
+
+        let old = versioned("1.0.0");
+        let mut new = versioned("1.0.0");
+        new.capabilities
+            .push(Capability::HostFunction("play_sound".to_owned()));
+        new.dependencies.push(Dependency {
+            id: "physics".to_owned(),
+            version: "^1".to_owned(),
+        });
+        assert_eq!(ManifestDiff::new(&old, &new).breaking().count(), 2);
+        assert!(!ManifestDiff::new(&new, &old).is_breaking());
+    }
+}
@@ -0,0 +1,185 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use bincode::error::{DecodeError, EncodeError};
+
+use crate::ModManifest;
+
+/// The on-disk format of manifests
+///
+/// A manifest starts with [`ModManifest::MAGIC`] and the little-endian
+/// [`ModManifest::FORMAT_VERSION`], followed by the manifest encoded with bincode. Manifests
+/// written before the format was versioned have no header, and are rejected.
+impl ModManifest {
+    pub const MAGIC: [u8; 4] = *b"HMAN";
+    /// Bumped whenever the layout of manifests changes
+    ///
+    /// Version 2 records the version of `bevy_harmonize_common` mods were built against
+    pub const FORMAT_VERSION: u16 = 2;
+    /// Manifests are small, anything bigger is rejected before allocating for it
+    pub const MAX_SIZE: usize = 16 * 1024 * 1024;
+
+    const HEADER_LEN: usize = Self::MAGIC.len() + size_of::<u16>();
+
+    pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&Self::MAGIC);
+        bytes.extend_from_slice(&Self::FORMAT_VERSION.to_le_bytes());
+
+        let body = bincode::encode_to_vec(self, bincode::config::standard())?;
+        bytes.extend(body);
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ManifestError> {
+        if bytes.len() > Self::MAX_SIZE {
+            return Err(ManifestError::TooLarge);
+        }
+
+        if bytes.len() < Self::HEADER_LEN || bytes[..Self::MAGIC.len()] != Self::MAGIC {
+            return Err(ManifestError::Unversioned);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != Self::FORMAT_VERSION {
+            return Err(ManifestError::UnsupportedVersion(version));
+        }
+
+        let body = &bytes[Self::HEADER_LEN..];
+        let config = bincode::config::standard().with_limit::<{ Self::MAX_SIZE }>();
+        let (manifest, read) =
+            bincode::decode_from_slice(body, config).map_err(|err| match err {
+                DecodeError::LimitExceeded => ManifestError::TooLarge,
+                err => ManifestError::Decode(err),
+            })?;
+        if read != body.len() {
+            return Err(ManifestError::TrailingBytes);
+        }
+
+        Ok(manifest)
+    }
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    /// The manifest, or a value within it, is bigger than [`ModManifest::MAX_SIZE`]
+    TooLarge,
+    /// The manifest was written for another version of the format
+    UnsupportedVersion(u16),
+    /// The manifest has no header, so it was written before the format was versioned
+    Unversioned,
+    Decode(DecodeError),
+    TrailingBytes,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge => write!(
+                f,
+                "Manifest is over the size limit of {} bytes",
+                ModManifest::MAX_SIZE
+            ),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported manifest format version {} (expected {})",
+                version,
+                ModManifest::FORMAT_VERSION
+            ),
+            Self::Unversioned => write!(
+                f,
+                "Manifest predates format versioning and can't be read, the mod needs to be rebuilt"
+            ),
+            Self::Decode(err) => write!(f, "Failed to decode manifest: {}", err),
+            Self::TrailingBytes => write!(f, "Trailing bytes after manifest"),
+        }
+    }
+}
+
+impl core::error::Error for ManifestError {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::ToOwned, vec};
+
+    use super::*;
+    use crate::{FileHash, StableId, TypeSignature};
+
+    fn manifest() -> ModManifest {
+        ModManifest {
+            wasm_hash: FileHash::empty(),
+            version: "1.0.0".to_owned(),
+            api_version: crate::VERSION.to_owned(),
+            common_version: crate::VERSION.to_owned(),
+            types: vec![TypeSignature::Opaque {
+                ty: StableId::new("core", "u32"),
+                size: Some(4),
+                align: Some(4),
+                generics: Vec::new(),
+            }],
+            features: Vec::new(),
+            capabilities: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = manifest().encode().unwrap();
+        assert_eq!(bytes[..4], ModManifest::MAGIC);
+        assert_eq!(ModManifest::decode(&bytes).unwrap(), manifest());
+    }
+
+    #[test]
+    fn unversioned_manifests_are_rejected() {
+        // A manifest written before the format was versioned, which only held a 16 byte wasm
+        // hash, an empty list of types and an empty list of features
+        let mut legacy = vec![7; 16];
+        legacy.extend_from_slice(&[0, 0]);
+        assert!(matches!(
+            ModManifest::decode(&legacy),
+            Err(ManifestError::Unversioned)
+        ));
+
+        let result = ModManifest::decode(&[1, 2, 3]);
+        assert!(matches!(result, Err(ManifestError::Unversioned)));
+    }
+
+    #[test]
+    fn newer_formats_are_rejected() {
+        let mut bytes = manifest().encode().unwrap();
+        bytes[4..6].copy_from_slice(&(ModManifest::FORMAT_VERSION + 1).to_le_bytes());
+
+        let result = ModManifest::decode(&bytes);
+        assert!(matches!(result, Err(ManifestError::UnsupportedVersion(3))));
+
+        bytes[4..6].copy_from_slice(&0u16.to_le_bytes());
+        let result = ModManifest::decode(&bytes);
+        assert!(matches!(result, Err(ManifestError::UnsupportedVersion(0))));
+
+        // Manifests laid out before the version of common was recorded
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+        let result = ModManifest::decode(&bytes);
+        assert!(matches!(result, Err(ManifestError::UnsupportedVersion(1))));
+    }
+
+    #[test]
+    fn decoding_is_bounded() {
+        let bytes = vec![0; ModManifest::MAX_SIZE + 1];
+        assert!(matches!(
+            ModManifest::decode(&bytes),
+            Err(ManifestError::TooLarge)
+        ));
+
+        // A length prefix claiming far more bytes than are available
+        let mut bytes = ModManifest::MAGIC.to_vec();
+        bytes.extend_from_slice(&ModManifest::FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(&[5, b'1', b'.', b'0', b'.', b'0']);
+        bytes.push(253);
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            ModManifest::decode(&bytes),
+            Err(ManifestError::TooLarge)
+        ));
+    }
+}
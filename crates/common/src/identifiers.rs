@@ -1,7 +1,33 @@
 use bevy_reflect::Reflect;
 
+/// Runs once when a mod is loaded, before [`Start`]
+#[derive(Reflect, Clone, Copy)]
+pub struct PreStart;
+
+/// Runs once when a mod is loaded
 #[derive(Reflect, Clone, Copy)]
 pub struct Start;
 
+/// Runs once when a mod is loaded, after [`Start`]
+#[derive(Reflect, Clone, Copy)]
+pub struct PostStart;
+
+/// Runs in bevy's `PreUpdate` schedule
+#[derive(Reflect, Clone, Copy)]
+pub struct PreUpdate;
+
+/// Runs in bevy's `Update` schedule
 #[derive(Reflect, Clone, Copy)]
 pub struct Update;
+
+/// Runs in bevy's `PostUpdate` schedule
+#[derive(Reflect, Clone, Copy)]
+pub struct PostUpdate;
+
+/// Runs in bevy's `FixedUpdate` schedule
+#[derive(Reflect, Clone, Copy)]
+pub struct FixedUpdate;
+
+/// Runs in bevy's `Last` schedule
+#[derive(Reflect, Clone, Copy)]
+pub struct Last;
@@ -0,0 +1,124 @@
+use alloc::string::{String, ToString};
+use core::fmt::Write;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{FileHash, ModManifest, SystemId};
+
+/// The human-readable format of manifests, meant for tooling
+///
+/// Every type is represented by its fields, except for hashes and system ids which are
+/// lowercase hex strings. A manifest converted to json and back is identical, so it encodes
+/// to the same bytes, see [`ModManifest::encode`].
+impl ModManifest {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl Serialize for SystemId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut hex = String::new();
+        write!(hex, "{:x}", self.0).unwrap();
+        serializer.serialize_str(&hex)
+    }
+}
+
+impl<'de> Deserialize<'de> for SystemId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        u64::from_str_radix(&hex, 16)
+            .map(Self)
+            .map_err(|_| de::Error::custom("system id is not a hex u64"))
+    }
+}
+
+impl Serialize for FileHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for FileHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() != 32 || !hex.is_ascii() {
+            return Err(de::Error::custom("file hash is not 32 hex digits"));
+        }
+
+        let mut hash = [0; 16];
+        for (index, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)
+                .map_err(|_| de::Error::custom("file hash is not 32 hex digits"))?;
+        }
+        Ok(Self(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::ToOwned, vec, vec::Vec};
+
+    use crate::*;
+
+    #[test]
+    fn round_trip_through_json() {
+        let system = System {
+            id: SystemId::from_type(core::any::TypeId::of::<u32>()),
+            name: "my_mod::update".to_owned(),
+            params: vec![Param::Res {
+                mutable: true,
+                id: StableId::new("my_mod", "Score").with_version("1.2.0"),
+            }],
+        };
+        let manifest = ModManifest {
+            wasm_hash: FileHash::from_sha256([7; 32]),
+            version: "1.2.0".to_owned(),
+            api_version: VERSION.to_owned(),
+            common_version: VERSION.to_owned(),
+            types: vec![TypeSignature::TupleStruct {
+                ty: StableId::new("my_mod", "Score").with_version("1.2.0"),
+                size: Some(4),
+                align: Some(4),
+                generics: Vec::new(),
+                fields: vec![StableId::new("unknown", "u32")],
+                layout: vec![FieldLayout { offset: 0, size: 4 }],
+            }],
+            features: vec![FeatureDescriptor {
+                name: "my_mod".to_owned(),
+                resources: vec![(StableId::new("my_mod", "Score"), vec![0])],
+                schedules: vec![ScheduleDescriptor {
+                    id: StableId::new("bevy_harmonize_common", "Update"),
+                    schedule: Schedule {
+                        systems: vec![system.clone()],
+                        constraints: vec![Constraint::Includes {
+                            parent_name: StableId::new("my_mod", "Gameplay"),
+                            set: SystemSet::Anonymous(vec![system.id]),
+                        }],
+                    },
+                }],
+            }],
+            capabilities: vec![Capability::HostFunction("play_sound".to_owned())],
+            dependencies: vec![Dependency {
+                id: "physics".to_owned(),
+                version: "^1".to_owned(),
+            }],
+        };
+
+        let json = manifest.to_json().unwrap();
+        assert!(json.contains(&alloc::format!("\"{}\"", manifest.wasm_hash)));
+
+        let parsed = ModManifest::from_json(&json).unwrap();
+        assert_eq!(parsed, manifest);
+        // Versions of ids are not part of their equality
+        assert_eq!(
+            parsed.types[0].stable_id().crate_version.as_deref(),
+            Some("1.2.0")
+        );
+        assert_eq!(parsed.encode().unwrap(), manifest.encode().unwrap());
+    }
+}
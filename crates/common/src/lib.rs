@@ -6,7 +6,11 @@ use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
-use core::{any::TypeId, fmt};
+use core::{
+    any::TypeId,
+    fmt,
+    hash::{Hash, Hasher},
+};
 
 use bevy_reflect::{DynamicTypePath, TypeInfo, TypePathTable, Typed};
 use bincode::{Decode, Encode};
@@ -20,14 +24,38 @@ pub use identifiers::*;
 mod type_signature;
 pub use type_signature::*;
 
+mod package;
+pub use package::*;
+
+mod format;
+pub use format::*;
+
+mod diff;
+pub use diff::*;
+
+#[cfg(feature = "serde")]
+mod json;
+
 mod utils;
 pub use utils::*;
 
+/// The version of this crate. Mods record the version they were built against, and only load
+/// into hosts built against a compatible one
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 /// Identify structs
-#[derive(Encode, Decode, PartialEq, Eq, Hash, Clone)]
+///
+/// The version of the crate declaring the type is known for types of mods and of the api.
+/// Two ids of the same type are equal whatever their versions, see [`StableId::is_compatible`]
+#[derive(Encode, Decode, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StableId {
     pub crate_name: String,
-    // pub crate_version: String, // TODO: add to bevy_reflect?
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub crate_version: Option<String>,
     pub name: String,
 }
 
@@ -35,6 +63,7 @@ impl StableId {
     pub fn new(crate_name: &str, name: &str) -> Self {
         StableId {
             crate_name: crate_name.to_string(),
+            crate_version: None,
             name: name.to_string(),
         }
     }
@@ -49,10 +78,7 @@ impl StableId {
     pub fn from_dynamic(dynamic: &impl DynamicTypePath) -> StableId {
         let crate_name = dynamic.reflect_crate_name().unwrap_or("unknown");
         let name = dynamic.reflect_short_type_path();
-        StableId {
-            crate_name: crate_name.to_string(),
-            name: name.to_string(),
-        }
+        Self::new(crate_name, name)
     }
 
     pub fn from_type_info(type_info: &TypeInfo) -> StableId {
@@ -62,16 +88,77 @@ impl StableId {
     pub fn from_type_path_table(path: &TypePathTable) -> StableId {
         let crate_name = path.crate_name().unwrap_or("unknown");
         let name = path.short_path();
-        StableId {
-            crate_name: crate_name.to_string(),
-            name: name.to_string(),
+        Self::new(crate_name, name)
+    }
+
+    pub fn with_version(mut self, crate_version: &str) -> Self {
+        self.crate_version = Some(crate_version.to_string());
+        self
+    }
+
+    /// Whether values of this type can be read as values of the other, which is the case
+    /// unless both versions are known and semver incompatible
+    pub fn is_compatible(&self, other: &StableId) -> bool {
+        if self != other {
+            return false;
+        }
+        match (&self.crate_version, &other.crate_version) {
+            (Some(version), Some(other)) => is_semver_compatible(version, other),
+            _ => true,
         }
     }
 }
 
+impl PartialEq for StableId {
+    fn eq(&self, other: &Self) -> bool {
+        self.crate_name == other.crate_name && self.name == other.name
+    }
+}
+
+impl Eq for StableId {}
+
+impl Hash for StableId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.crate_name.hash(state);
+        self.name.hash(state);
+    }
+}
+
 impl fmt::Debug for StableId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "StableId(\"{}::{}\")", self.crate_name, self.name)
+        match &self.crate_version {
+            Some(version) => write!(
+                f,
+                "StableId(\"{}@{}::{}\")",
+                self.crate_name, version, self.name
+            ),
+            None => write!(f, "StableId(\"{}::{}\")", self.crate_name, self.name),
+        }
+    }
+}
+
+/// Formats the id as a path, without the version
+impl fmt::Display for StableId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.crate_name, self.name)
+    }
+}
+
+/// Whether two versions are compatible following cargo's flavor of semver, where the
+/// leftmost non-zero component is the one with breaking changes
+///
+/// Versions that aren't valid semver are only compatible with themselves
+pub fn is_semver_compatible(a: &str, b: &str) -> bool {
+    let (Ok(a), Ok(b)) = (semver::Version::parse(a), semver::Version::parse(b)) else {
+        return a == b;
+    };
+    if !a.pre.is_empty() || !b.pre.is_empty() {
+        return a == b;
+    }
+    match (a.major, a.minor) {
+        (0, 0) => b.major == 0 && b.minor == 0 && a.patch == b.patch,
+        (0, minor) => b.major == 0 && b.minor == minor,
+        (major, _) => b.major == major,
     }
 }
 
@@ -79,9 +166,6 @@ impl fmt::Debug for StableId {
 #[derive(Encode, Decode, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 pub struct SystemId(u64);
 
-// Custom hasher that just returns bytes
-use core::hash::{Hash, Hasher};
-
 impl SystemId {
     pub fn of<T: ?Sized + 'static>() -> Self {
         Self::from_type(TypeId::of::<T>())
@@ -102,13 +186,45 @@ impl fmt::Debug for SystemId {
 }
 
 #[derive(Encode, Decode, PartialEq, Eq, Debug, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Param {
     Command,
     Res { mutable: bool, id: StableId },
     // TODO: Query, etc
 }
 
+/// A privilege a mod must be granted by the host before it can be loaded
+#[derive(Encode, Decode, PartialEq, Eq, Hash, Debug, Clone, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Capability {
+    /// Spawn entities in the host's world
+    SpawnEntities,
+    /// Call a custom function registered by the host, by name. Mods import it from the
+    /// `bevy_harmonize_host` module
+    HostFunction(String),
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SpawnEntities => write!(f, "SpawnEntities"),
+            Self::HostFunction(name) => write!(f, "HostFunction({:?})", name),
+        }
+    }
+}
+
+/// Another mod that must be started before the mod depending on it
+#[derive(Encode, Decode, PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dependency {
+    /// The package name of the mod, or a uri it can be loaded from
+    pub id: String,
+    /// A semver requirement on the version of the mod
+    pub version: String,
+}
+
 #[derive(Encode, Decode, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FeatureDescriptor {
     pub name: String,
     pub resources: Vec<(StableId, Vec<u8>)>,
@@ -116,10 +232,22 @@ pub struct FeatureDescriptor {
 }
 
 #[derive(Encode, Decode, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModManifest {
     pub wasm_hash: FileHash,
+    /// The semver version of the mod
+    pub version: String,
+    /// The version of `bevy_harmonize_api` the mod was built against
+    pub api_version: String,
+    /// The version of `bevy_harmonize_common` the mod was built against, which defines how
+    /// the mod and the host talk to each other
+    pub common_version: String,
     pub types: Vec<TypeSignature>,
     pub features: Vec<FeatureDescriptor>,
+    /// Every capability the mod requires, sorted and deduplicated
+    pub capabilities: Vec<Capability>,
+    /// The mods this one depends on, in the order they were declared
+    pub dependencies: Vec<Dependency>,
 }
 
 impl ModManifest {
@@ -145,11 +273,16 @@ pub struct FileHash([u8; 16]);
 
 impl fmt::Debug for FileHash {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "FileHash(\"")?;
+        write!(f, "FileHash(\"{}\")", self)
+    }
+}
+
+/// Formats the hash as lowercase hex
+impl fmt::Display for FileHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for byte in self.0.iter() {
             write!(f, "{:02x}", byte)?;
         }
-        write!(f, "\")")?;
         Ok(())
     }
 }
@@ -0,0 +1,216 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use bincode::{
+    error::{DecodeError, EncodeError},
+    Decode, Encode,
+};
+
+/// A mod packed into a single file, ready to be shared with players
+///
+/// On disk, a package starts with [`ModPackage::MAGIC`] and the little-endian
+/// [`ModPackage::FORMAT_VERSION`], followed by the package encoded with bincode.
+#[derive(Encode, Decode, PartialEq, Debug, Clone)]
+pub struct ModPackage {
+    /// Free-form information about the mod, such as its author or description
+    pub metadata: Vec<(String, String)>,
+    /// The encoded [`crate::ModManifest`]
+    pub manifest: Vec<u8>,
+    pub wasm: Vec<u8>,
+    /// Files shipped with the mod, keyed by their path relative to the package
+    pub assets: Vec<(String, Vec<u8>)>,
+    pub signature: Option<PackageSignature>,
+}
+
+/// An ed25519 signature of a package's [signed bytes](ModPackage::signed_bytes)
+#[derive(Encode, Decode, PartialEq, Debug, Clone)]
+pub struct PackageSignature {
+    /// The public key of the signer
+    pub public_key: [u8; 32],
+    pub signature: [u8; 64],
+}
+
+impl ModPackage {
+    /// The file extension of packages, without the preceding dot
+    pub const EXTENSION: &str = "hmod";
+    pub const MAGIC: [u8; 4] = *b"HMOD";
+    /// Bumped whenever the layout of packages changes
+    pub const FORMAT_VERSION: u16 = 2;
+    /// Anything bigger is rejected before allocating for it, since packages are untrusted
+    pub const MAX_SIZE: usize = 1024 * 1024 * 1024;
+
+    const HEADER_LEN: usize = Self::MAGIC.len() + size_of::<u16>();
+
+    /// Creates an unsigned package holding only a manifest and its wasm module
+    pub fn new(manifest: Vec<u8>, wasm: Vec<u8>) -> Self {
+        Self {
+            metadata: Vec::new(),
+            manifest,
+            wasm,
+            assets: Vec::new(),
+            signature: None,
+        }
+    }
+
+    /// Returns the bytes covered by the package's signature, which is everything but the
+    /// signature itself
+    pub fn signed_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&Self::MAGIC);
+        bytes.extend_from_slice(&Self::FORMAT_VERSION.to_le_bytes());
+
+        let content = (&self.metadata, &self.manifest, &self.wasm, &self.assets);
+        let body = bincode::encode_to_vec(content, bincode::config::standard())?;
+        bytes.extend(body);
+        Ok(bytes)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&Self::MAGIC);
+        bytes.extend_from_slice(&Self::FORMAT_VERSION.to_le_bytes());
+
+        let body = bincode::encode_to_vec(self, bincode::config::standard())?;
+        bytes.extend(body);
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, PackageError> {
+        if bytes.len() > Self::MAX_SIZE {
+            return Err(PackageError::TooLarge);
+        }
+        if bytes.len() < Self::HEADER_LEN || bytes[..Self::MAGIC.len()] != Self::MAGIC {
+            return Err(PackageError::InvalidMagic);
+        }
+
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != Self::FORMAT_VERSION {
+            return Err(PackageError::UnsupportedVersion(version));
+        }
+
+        let config = bincode::config::standard().with_limit::<{ Self::MAX_SIZE }>();
+        let (package, read) = bincode::decode_from_slice(&bytes[Self::HEADER_LEN..], config)
+            .map_err(|err| match err {
+                DecodeError::LimitExceeded => PackageError::TooLarge,
+                err => PackageError::Decode(err),
+            })?;
+        if Self::HEADER_LEN + read != bytes.len() {
+            return Err(PackageError::TrailingBytes);
+        }
+
+        Ok(package)
+    }
+}
+
+#[derive(Debug)]
+pub enum PackageError {
+    /// The package, or a value within it, is bigger than [`ModPackage::MAX_SIZE`]
+    TooLarge,
+    /// The bytes do not start with [`ModPackage::MAGIC`], so they are not a package
+    InvalidMagic,
+    /// The package was made for a different version of the format
+    UnsupportedVersion(u16),
+    Decode(DecodeError),
+    TrailingBytes,
+}
+
+impl fmt::Display for PackageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge => write!(
+                f,
+                "Mod package is over the size limit of {} bytes",
+                ModPackage::MAX_SIZE
+            ),
+            Self::InvalidMagic => write!(f, "Not a mod package"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported mod package format version {} (expected {})",
+                version,
+                ModPackage::FORMAT_VERSION
+            ),
+            Self::Decode(err) => write!(f, "Failed to decode mod package: {}", err),
+            Self::TrailingBytes => write!(f, "Trailing bytes after mod package"),
+        }
+    }
+}
+
+impl core::error::Error for PackageError {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::ToOwned, vec};
+
+    use super::*;
+
+    fn package() -> ModPackage {
+        ModPackage {
+            metadata: vec![("author".to_owned(), "me".to_owned())],
+            manifest: vec![1, 2, 3],
+            wasm: vec![4, 5],
+            assets: vec![("textures/cube.png".to_owned(), vec![6])],
+            signature: Some(PackageSignature {
+                public_key: [7; 32],
+                signature: [8; 64],
+            }),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = package().encode().unwrap();
+        assert_eq!(bytes[..4], ModPackage::MAGIC);
+        assert_eq!(ModPackage::decode(&bytes).unwrap(), package());
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        let mut bytes = package().encode().unwrap();
+        bytes[0] = b'X';
+        assert!(matches!(
+            ModPackage::decode(&bytes),
+            Err(PackageError::InvalidMagic)
+        ));
+        assert!(matches!(
+            ModPackage::decode(b"HMO"),
+            Err(PackageError::InvalidMagic)
+        ));
+
+        let mut bytes = package().encode().unwrap();
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+        assert!(matches!(
+            ModPackage::decode(&bytes),
+            Err(PackageError::UnsupportedVersion(1))
+        ));
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let mut bytes = package().encode().unwrap();
+        bytes.push(0);
+        assert!(matches!(
+            ModPackage::decode(&bytes),
+            Err(PackageError::TrailingBytes)
+        ));
+    }
+
+    #[test]
+    fn decoding_is_bounded() {
+        let bytes = vec![0; ModPackage::MAX_SIZE + 1];
+        assert!(matches!(
+            ModPackage::decode(&bytes),
+            Err(PackageError::TooLarge)
+        ));
+
+        // No metadata, then a manifest claiming far more bytes than are available
+        let mut bytes = ModPackage::MAGIC.to_vec();
+        bytes.extend_from_slice(&ModPackage::FORMAT_VERSION.to_le_bytes());
+        bytes.push(0);
+        bytes.push(253);
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            ModPackage::decode(&bytes),
+            Err(PackageError::TooLarge)
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 use super::*;
 
 #[derive(Encode, Decode, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ScheduleDescriptor {
     pub id: StableId,
     pub schedule: Schedule,
@@ -8,6 +9,7 @@ pub struct ScheduleDescriptor {
 
 /// Describes how to create a schedule
 #[derive(Encode, Decode, PartialEq, Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Schedule {
     pub systems: Vec<System>,
     pub constraints: Vec<Constraint>,
@@ -17,6 +19,7 @@ pub struct Schedule {
 ///
 /// These must always be checked for validity before being loaded by the modloader
 #[derive(Encode, Decode, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Constraint {
     /// One system set needs to run before another system set
     Order { before: SystemSet, after: SystemSet },
@@ -30,6 +33,7 @@ pub enum Constraint {
 }
 
 #[derive(Encode, Decode, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct System {
     pub id: SystemId,
     pub name: String,
@@ -37,6 +41,7 @@ pub struct System {
 }
 
 #[derive(Encode, Decode, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SystemSet {
     Anonymous(Vec<SystemId>),
     Named(StableId),
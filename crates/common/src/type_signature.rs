@@ -1,10 +1,77 @@
 use alloc::{string::String, vec::Vec};
 use bincode::{Decode, Encode};
+use core::{fmt, ops::Range};
 
 use crate::StableId;
 
+/// The most memory the addressable types of a mod may take together, in bytes
+///
+/// The host allocates every value in full, so manifests can't be trusted with more
+pub const MAX_ADDRESSABLE_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Gives each addressable type a range of wasm memory that overlaps no other
+///
+/// Ranges are allocated downward from `u32::MAX`, in the order of the given types. Mods access
+/// a type at its range within the memory imported for it, so the build and the runtime must
+/// agree on this layout.
+pub fn type_addresses(
+    types: &[TypeSignature],
+) -> Result<Vec<(&TypeSignature, Range<u32>)>, AddressError> {
+    let mut address: u32 = u32::MAX;
+    types
+        .iter()
+        // Take all types with known size and alignment
+        .filter_map(|ty| match (ty.size(), ty.align()) {
+            (Some(size), Some(align)) => Some((ty, size, align)),
+            _ => None,
+        })
+        // Size should be a multiple of the alignment
+        // Alignment must be a power of 2, greater than 0, less than or equal to 128
+        // Size must be greater than 0
+        .filter(|(_, size, align)| {
+            *size > 0 && *align > 0 && size % align == 0 && align.is_power_of_two() && *align <= 128
+        })
+        // Give them each their own unique address range
+        .map(|(ty, size, align)| {
+            let too_large = || AddressError::TooLarge(ty.stable_id());
+            let size = u32::try_from(size).map_err(|_| too_large())?;
+            // Ensure it doesn't overlap with the previous type
+            let start = address.checked_sub(size).ok_or_else(too_large)?;
+            // Ensure the address is aligned to the next multiple of the alignment
+            let start = start - start % align as u32;
+            if u32::MAX - start > MAX_ADDRESSABLE_SIZE {
+                return Err(too_large());
+            }
+
+            address = start;
+            Ok((ty, start..start + size))
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum AddressError {
+    /// The type doesn't fit in the [`MAX_ADDRESSABLE_SIZE`] left by the types before it
+    TooLarge(StableId),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge(ty) => write!(
+                f,
+                "{} doesn't fit in memory, addressable types may take at most {} bytes",
+                ty, MAX_ADDRESSABLE_SIZE
+            ),
+        }
+    }
+}
+
+impl core::error::Error for AddressError {}
+
 /// A serializable version of [`TypeInfo`]
 #[derive(Encode, Decode, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TypeSignature {
     Struct {
         ty: StableId,
@@ -12,6 +79,7 @@ pub enum TypeSignature {
         align: Option<usize>,
         generics: Vec<GenericSignature>,
         fields: Vec<FieldSignature>,
+        layout: Vec<FieldLayout>,
     },
     TupleStruct {
         ty: StableId,
@@ -19,6 +87,7 @@ pub enum TypeSignature {
         align: Option<usize>,
         generics: Vec<GenericSignature>,
         fields: Vec<StableId>,
+        layout: Vec<FieldLayout>,
     },
     Tuple {
         ty: StableId,
@@ -26,6 +95,7 @@ pub enum TypeSignature {
         align: Option<usize>,
         generics: Vec<GenericSignature>,
         fields: Vec<StableId>,
+        layout: Vec<FieldLayout>,
     },
     List {
         ty: StableId,
@@ -80,6 +150,20 @@ impl TypeSignature {
         }
     }
 
+    pub fn stable_id_mut(&mut self) -> &mut StableId {
+        match self {
+            TypeSignature::Struct { ty, .. }
+            | TypeSignature::TupleStruct { ty, .. }
+            | TypeSignature::Tuple { ty, .. }
+            | TypeSignature::List { ty, .. }
+            | TypeSignature::Array { ty, .. }
+            | TypeSignature::Map { ty, .. }
+            | TypeSignature::Set { ty, .. }
+            | TypeSignature::Enum { ty, .. }
+            | TypeSignature::Opaque { ty, .. } => ty,
+        }
+    }
+
     /// Returns the size of the type
     pub fn size(&self) -> Option<usize> {
         match self {
@@ -109,10 +193,28 @@ impl TypeSignature {
             | TypeSignature::Set { .. } => None,
         }
     }
+
+    /// Returns the memory layout of each field, in the same order as the fields
+    ///
+    /// Empty if the type has no fields, or if the layout was never observed
+    pub fn layout(&self) -> &[FieldLayout] {
+        match self {
+            TypeSignature::Struct { layout, .. }
+            | TypeSignature::TupleStruct { layout, .. }
+            | TypeSignature::Tuple { layout, .. } => layout,
+            TypeSignature::List { .. }
+            | TypeSignature::Array { .. }
+            | TypeSignature::Map { .. }
+            | TypeSignature::Set { .. }
+            | TypeSignature::Enum { .. }
+            | TypeSignature::Opaque { .. } => &[],
+        }
+    }
 }
 
 /// A serializable version of [`bevy_reflect::GenericInfo`]
 #[derive(Encode, Decode, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GenericSignature {
     Type(StableId),
     Const(StableId),
@@ -120,13 +222,25 @@ pub enum GenericSignature {
 
 /// A serializable version of [`bevy_reflect::NamedField`]
 #[derive(Encode, Decode, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldSignature {
     pub name: String,
     pub ty: StableId,
 }
 
+/// Where a field lives within the memory of its parent
+///
+/// Rust does not guarantee field order in memory, so this is observed from a real value
+#[derive(Encode, Decode, PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldLayout {
+    pub offset: usize,
+    pub size: usize,
+}
+
 /// A serializable version of [`bevy_reflect::VariantInfo`]
 #[derive(Encode, Decode, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VariantSignature {
     Struct {
         name: String,
@@ -140,3 +254,36 @@ pub enum VariantSignature {
         name: String,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn opaque(name: &str, size: usize) -> TypeSignature {
+        TypeSignature::Opaque {
+            ty: StableId::new("test_crate", name),
+            size: Some(size),
+            align: Some(1),
+            generics: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn addresses_are_bounded() {
+        let half = MAX_ADDRESSABLE_SIZE as usize / 2;
+        let types = vec![opaque("a", half), opaque("b", half)];
+        let addresses = type_addresses(&types).unwrap();
+        assert_eq!(addresses[1].1.start, u32::MAX - MAX_ADDRESSABLE_SIZE);
+
+        let types = vec![opaque("a", half), opaque("b", half), opaque("c", 1)];
+        let result = type_addresses(&types);
+        assert!(matches!(result, Err(AddressError::TooLarge(ty)) if ty.name == "c"));
+
+        for size in [u32::MAX as usize, usize::MAX] {
+            let types = vec![opaque("huge", size)];
+            assert!(type_addresses(&types).is_err());
+        }
+    }
+}
@@ -17,7 +17,7 @@ pub fn derive_addressable(input: TokenStream) -> TokenStream {
             // 1. Before resolving the manifest, __imports::__resolve_address returns a dangling placeholder pointer
             // 2. Allocate unique, non-overlapping ranges for each struct
             // 3. Then __resolve_address is adjusted to return pointers for each struct
-            // 4. Finally, as a post-compilation step, we find instructions that read/write to these addresses and correct the address space and rebase the pointer from zero
+            // 4. Finally, as a post-compilation step, we find instructions that read/write to these addresses and correct the address space, keeping the address so the runtime stores the value at it
             const PTR: *mut Self = __imports::__resolve_address::<#ident>();
         }
     };
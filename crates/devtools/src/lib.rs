@@ -9,6 +9,7 @@ use bevy_ecs::{
 use bevy_ecs_macros::Resource;
 use bevy_harmonize_build::build;
 use bevy_tasks::{block_on, poll_once, AsyncComputeTaskPool, Task};
+use ed25519_dalek::SigningKey;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use tracing::{error, info};
 
@@ -25,6 +26,11 @@ pub struct ModDevtoolsPlugin {
     ///
     /// Defaults to `./mods`.
     pub watch_dir: PathBuf,
+
+    /// The key used to sign built mods. Mods are left unsigned when this is `None`.
+    ///
+    /// Defaults to `None`.
+    pub signing_key: Option<SigningKey>,
 }
 
 impl Default for ModDevtoolsPlugin {
@@ -32,6 +38,7 @@ impl Default for ModDevtoolsPlugin {
         Self {
             cargo_dir: PathBuf::from("."),
             watch_dir: PathBuf::from("./mods"),
+            signing_key: None,
         }
     }
 }
@@ -41,6 +48,7 @@ impl Plugin for ModDevtoolsPlugin {
         app.insert_resource(BuildSettings {
             cargo_dir: self.cargo_dir.clone(),
             watch_dir: self.watch_dir.clone(),
+            signing_key: self.signing_key.clone(),
         })
         .init_resource::<BuildTask>()
         .add_systems(PreStartup, update_build)
@@ -52,6 +60,7 @@ impl Plugin for ModDevtoolsPlugin {
 struct BuildSettings {
     cargo_dir: PathBuf,
     watch_dir: PathBuf,
+    signing_key: Option<SigningKey>,
 }
 
 #[derive(Resource)]
@@ -127,7 +136,9 @@ fn update_build(settings: Res<BuildSettings>, mut task: ResMut<BuildTask>, mut m
         let mods_directory = settings.watch_dir.clone();
         let cargo_directory = settings.cargo_dir.clone();
 
-        let future = build(release, mods_directory, cargo_directory);
+        let signing_key = settings.signing_key.clone();
+
+        let future = build(release, mods_directory, cargo_directory, signing_key);
         task.compute
             .replace(AsyncComputeTaskPool::get().spawn(future));
     }
@@ -4,10 +4,16 @@
 extern crate alloc;
 use core::any::TypeId;
 
-use alloc::{borrow::ToOwned, collections::BTreeMap, vec};
+use alloc::{
+    borrow::ToOwned,
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
 
-use api::schema::Schema;
-use common::{FeatureDescriptor, FileHash, ModManifest, ScheduleDescriptor, StableId};
+use api::schema::{self, Schema};
+use common::{
+    Capability, Dependency, FeatureDescriptor, FileHash, ModManifest, ScheduleDescriptor, StableId,
+};
 
 mod type_signatures;
 use type_signatures::TypeSignatures;
@@ -18,24 +24,29 @@ pub fn schema_to_manifest(schema: Schema) -> ModManifest {
         types.register_type(ty);
     }
 
-    // There can only be one default per resource
+    // There can only be one default per resource, owned by the first feature adding it
     let mut resources = BTreeMap::new();
-    for (type_info, value) in schema.resources() {
+    for (type_info, value, layouts, feature) in schema.resources() {
         let id = StableId::from_type_info(type_info);
-        resources.insert(type_info.type_id(), (id, value));
+        resources
+            .entry(type_info.type_id())
+            .or_insert((feature, id, value));
+
+        for (type_id, layout) in layouts {
+            types.set_layout(type_id, layout);
+        }
     }
-    let resources = resources.into_values().collect();
 
-    // Combine schedules with the same label together
-    let mut schedules: BTreeMap<TypeId, ScheduleDescriptor> = BTreeMap::new();
-    for (type_info, schedule) in schema.schedules() {
+    // Combine schedules of a feature with the same label together
+    let mut schedules: BTreeMap<(usize, TypeId), ScheduleDescriptor> = BTreeMap::new();
+    for (type_info, schedule, feature) in schema.schedules() {
         let id = StableId::from_type_info(type_info);
         let default = ScheduleDescriptor {
             id,
             schedule: schedule.clone(),
         };
         schedules
-            .entry(type_info.type_id())
+            .entry((feature, type_info.type_id()))
             .and_modify(|descriptor| {
                 let common::Schedule {
                     systems,
@@ -48,25 +59,77 @@ pub fn schema_to_manifest(schema: Schema) -> ModManifest {
             })
             .or_insert(default);
     }
-    let schedules = schedules.into_values().collect();
 
-    ModManifest {
+    // The mod itself is the first feature
+    let names = core::iter::once(schema.name().unwrap_or("unknown"))
+        .chain(schema.features().iter().copied());
+    let mut features: Vec<_> = names
+        .map(|name| FeatureDescriptor {
+            name: name.to_owned(),
+            resources: Vec::new(),
+            schedules: Vec::new(),
+        })
+        .collect();
+    for (feature, id, value) in resources.into_values() {
+        features[feature].resources.push((id, value));
+    }
+    for ((feature, _), descriptor) in schedules {
+        features[feature].schedules.push(descriptor);
+    }
+
+    let capabilities: BTreeSet<_> = schema
+        .capabilities()
+        .iter()
+        .map(|capability| match capability {
+            schema::Capability::SpawnEntities => Capability::SpawnEntities,
+            schema::Capability::HostFunction(name) => Capability::HostFunction((*name).to_owned()),
+        })
+        .collect();
+
+    let mut manifest = ModManifest {
         wasm_hash: FileHash::empty(),
+        version: schema.version().to_owned(),
+        api_version: schema.api_version().to_owned(),
+        common_version: common::VERSION.to_owned(),
         types: types.into_vec(),
-        features: vec![FeatureDescriptor {
-            name: schema.name().unwrap_or("unknown").to_owned(),
-            resources,
-            schedules,
-        }],
+        features,
+        capabilities: capabilities.into_iter().collect(),
+        dependencies: schema
+            .dependencies()
+            .iter()
+            .map(|dependency| Dependency {
+                id: dependency.id.to_owned(),
+                version: dependency.version.to_owned(),
+            })
+            .collect(),
+    };
+
+    // Types declared by the modding library take the version of their crate
+    set_crate_version(&mut manifest, "bevy_harmonize_api", schema.api_version());
+    set_crate_version(&mut manifest, "bevy_harmonize_common", common::VERSION);
+    manifest
+}
+
+/// Records the version of a crate in the ids of the types it declares, so the loader can
+/// tell whether values of those types are compatible across versions of a mod
+pub fn set_crate_version(manifest: &mut ModManifest, crate_name: &str, version: &str) {
+    for signature in manifest.types.iter_mut() {
+        let id = signature.stable_id_mut();
+        if id.crate_name == crate_name {
+            id.crate_version = Some(version.to_owned());
+        }
     }
 }
 
 // Tests
 #[cfg(test)]
 mod tests {
-    use alloc::{borrow::ToOwned, string::String, vec::Vec};
+    use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
     use api::prelude::*;
-    use common::{FieldSignature, Param, Schedule, Start, System, TypeSignature, VariantSignature};
+    use common::{
+        FieldLayout, FieldSignature, Param, Schedule, Start, System, TypeSignature,
+        VariantSignature,
+    };
 
     use super::*;
 
@@ -80,6 +143,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn manifest_capabilities() {
+        const SCHEMA: Schema = Mod::new("Test capabilities")
+            .require_capability(schema::Capability::HostFunction("play_sound"))
+            .require_capability(schema::Capability::SpawnEntities)
+            .require_capability(schema::Capability::SpawnEntities)
+            .into_schema();
+
+        // Sorted and deduplicated
+        assert_eq!(
+            schema_to_manifest(SCHEMA).capabilities,
+            vec![
+                common::Capability::SpawnEntities,
+                common::Capability::HostFunction("play_sound".to_owned())
+            ]
+        );
+    }
+
     #[test]
     fn manifest_from_schema() {
         #[derive(Reflect)]
@@ -119,9 +200,21 @@ mod tests {
         let ModManifest {
             types,
             features,
+            capabilities,
+            dependencies,
+            version,
+            api_version,
+            common_version,
             wasm_hash: _wasm_hash,
         } = schema_to_manifest(SCHEMA);
 
+        assert_eq!(version, "0.0.0");
+        assert_eq!(api_version, api::VERSION);
+        assert_eq!(common_version, common::VERSION);
+
+        assert!(capabilities.is_empty());
+        assert!(dependencies.is_empty());
+
         assert_eq!(types.len(), 4);
         // In indeterminate order
         assert!(types.contains(&TypeSignature::Struct {
@@ -138,6 +231,16 @@ mod tests {
                     name: "bar".to_owned(),
                     ty: StableId::from_typed::<MyEnum>()
                 }
+            ],
+            layout: vec![
+                FieldLayout {
+                    offset: core::mem::offset_of!(MyStruct, foo),
+                    size: size_of::<u32>(),
+                },
+                FieldLayout {
+                    offset: core::mem::offset_of!(MyStruct, bar),
+                    size: size_of::<MyEnum>(),
+                }
             ]
         }));
         assert!(types.contains(&TypeSignature::Enum {
@@ -195,4 +298,70 @@ mod tests {
             }]
         )
     }
+
+    #[test]
+    fn manifest_features() {
+        #[derive(Reflect, Default)]
+        struct Score(u32);
+
+        unsafe impl Addressable for Score {}
+
+        fn system1() {}
+        fn system2() {}
+
+        const SCHEMA: Schema = Mod::new("Test features")
+            .add_systems(Start, system1)
+            .add_feature("scoring")
+            .add_resource::<Score>()
+            .add_systems(Start, system2)
+            .into_schema();
+
+        let features = schema_to_manifest(SCHEMA).features;
+        assert_eq!(
+            features
+                .iter()
+                .map(|feature| feature.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Test features", "scoring"]
+        );
+        assert!(features[0].resources.is_empty());
+        assert_eq!(
+            features[0].schedules[0].schedule.systems,
+            vec![make_system(system1, Vec::new())]
+        );
+        assert_eq!(
+            features[1].resources,
+            vec![(StableId::from_typed::<Score>(), vec![0])]
+        );
+        assert_eq!(
+            features[1].schedules[0].schedule.systems,
+            vec![make_system(system2, Vec::new())]
+        );
+    }
+
+    #[test]
+    fn manifest_crate_versions() {
+        #[derive(Reflect, Default)]
+        struct Score(u32);
+
+        unsafe impl Addressable for Score {}
+
+        const SCHEMA: Schema = Mod::new("Test versions")
+            .version("1.4.0")
+            .add_resource::<Score>()
+            .into_schema();
+
+        let mut manifest = schema_to_manifest(SCHEMA);
+        assert_eq!(manifest.version, "1.4.0");
+
+        let crate_name = StableId::from_typed::<Score>().crate_name;
+        set_crate_version(&mut manifest, &crate_name, SCHEMA.version());
+        let score = manifest
+            .types
+            .iter()
+            .map(|signature| signature.stable_id())
+            .find(|id| *id == StableId::from_typed::<Score>())
+            .unwrap();
+        assert_eq!(score.crate_version.as_deref(), Some("1.4.0"));
+    }
 }
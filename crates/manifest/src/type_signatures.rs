@@ -5,7 +5,9 @@ use alloc::{borrow::ToOwned, collections::BTreeMap, vec::Vec};
 
 use api::schema::Type as SchemaType;
 use bevy_reflect::{GenericInfo, Generics, Type, TypeInfo, VariantInfo};
-use common::{FieldSignature, GenericSignature, StableId, TypeSignature, VariantSignature};
+use common::{
+    FieldLayout, FieldSignature, GenericSignature, StableId, TypeSignature, VariantSignature,
+};
 
 pub(crate) struct TypeSignatures(BTreeMap<TypeId, TypeSignature>);
 
@@ -54,6 +56,7 @@ impl TypeSignatures {
                         align,
                         generics: generics(info.generics()),
                         fields,
+                        layout: Vec::new(),
                     }
                 }
                 TypeInfo::TupleStruct(info) => {
@@ -75,6 +78,7 @@ impl TypeSignatures {
                         size,
                         generics: generics(info.generics()),
                         fields,
+                        layout: Vec::new(),
                     }
                 }
                 TypeInfo::Tuple(info) => {
@@ -96,6 +100,7 @@ impl TypeSignatures {
                         align,
                         generics: generics(info.generics()),
                         fields,
+                        layout: Vec::new(),
                     }
                 }
                 TypeInfo::List(info) => {
@@ -207,6 +212,16 @@ impl TypeSignatures {
         }
     }
 
+    /// Records the observed memory layout of a struct-like type's fields
+    pub fn set_layout(&mut self, type_id: TypeId, field_layout: Vec<FieldLayout>) {
+        match self.0.get_mut(&type_id) {
+            Some(TypeSignature::Struct { layout, .. })
+            | Some(TypeSignature::TupleStruct { layout, .. })
+            | Some(TypeSignature::Tuple { layout, .. }) => *layout = field_layout,
+            _ => {}
+        }
+    }
+
     pub fn into_vec(self) -> Vec<TypeSignature> {
         self.0.into_values().collect()
     }
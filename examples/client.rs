@@ -8,7 +8,7 @@ fn main() {
     App::new()
         .add_plugins((
             DefaultPlugins,
-            ModLoaderPlugin,
+            ModLoaderPlugin::default(),
             ModDevtoolsPlugin {
                 // Watches and builds the mods found the `./examples/mods` directory
                 watch_dir: PathBuf::from("./examples/mods"),
@@ -0,0 +1,101 @@
+use anyhow::*;
+use bevy_asset::{io::Reader, Asset, AssetEvent, AssetLoader, Assets, LoadContext};
+use bevy_ecs::{
+    event::EventReader,
+    system::{Res, ResMut},
+};
+use bevy_reflect::TypePath;
+use common::ModPackage;
+use tracing::warn;
+
+use crate::{loaded::package_name, mods::Mods};
+
+/// The files of a mod, loaded through the asset server
+///
+/// Mods are loaded as soon as their asset is, reloaded whenever it is modified, and
+/// unloaded once it is removed
+#[derive(Asset, TypePath, Debug)]
+pub struct ModAsset {
+    /// The package name of the mod
+    pub name: String,
+    pub(crate) package: ModPackage,
+}
+
+/// Loads a [`ModAsset`] from a ".hmod" package, or from a ".manifest" file and its sibling
+/// ".wasm" file
+#[derive(Default)]
+pub struct ModAssetLoader;
+
+impl AssetLoader for ModAssetLoader {
+    type Asset = ModAsset;
+    type Settings = ();
+    type Error = Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<ModAsset> {
+        let name = package_name(load_context.path());
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let is_package = load_context
+            .path()
+            .extension()
+            .is_some_and(|ext| ext == ModPackage::EXTENSION);
+        if is_package {
+            let package = ModPackage::decode(&bytes)?;
+            return Ok(ModAsset { name, package });
+        }
+
+        // Reading the wasm file through the load context also reloads the asset when it changes
+        let manifest = bytes;
+        let wasm_path = load_context
+            .asset_path()
+            .resolve_embed(&format!("{}.wasm", name))?;
+        let wasm = load_context
+            .read_asset_bytes(&wasm_path)
+            .await
+            .with_context(|| format!("Failed to read wasm file {}", wasm_path))?;
+
+        Ok(ModAsset {
+            name,
+            package: ModPackage::new(manifest, wasm),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[ModPackage::EXTENSION, "manifest"]
+    }
+}
+
+/// Loads, reloads and unloads mods following the events of their assets
+pub(crate) fn handle_mod_assets(
+    mut mods: ResMut<Mods>,
+    assets: Res<Assets<ModAsset>>,
+    mut events: EventReader<AssetEvent<ModAsset>>,
+) {
+    for event in events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                let Some(asset) = assets.get(*id) else {
+                    continue;
+                };
+                let handle = mods.load_from_asset(asset);
+                mods.assets.insert(*id, handle);
+            }
+            AssetEvent::Removed { id } => {
+                let Some(handle) = mods.assets.remove(id) else {
+                    continue;
+                };
+                if let Err(err) = mods.unload(handle) {
+                    warn!("Failed to unload mod of removed asset:\n{:?}", err);
+                }
+            }
+            AssetEvent::Unused { .. } | AssetEvent::LoadedWithDependencies { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+
+use bevy_platform::collections::HashMap;
+
+/// Limits on how much work a mod may do, measured in wasmtime fuel
+///
+/// Fuel is consumed roughly one unit per wasm instruction, so a system stuck in a loop is
+/// interrupted instead of hanging the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionBudget {
+    /// Fuel a single system call may consume before it is interrupted
+    pub per_system: u64,
+    /// Fuel every system of the mod may consume in a single run of a schedule. Systems left
+    /// once it is used up are skipped, each reported with a [`ScheduleBudgetExceeded`] trap
+    pub per_schedule: Option<u64>,
+    /// Suspend the mod once one of its systems goes over budget, until it is resumed or
+    /// reloaded
+    pub suspend_on_exhaustion: bool,
+}
+
+impl ExecutionBudget {
+    pub const DEFAULT_FUEL_PER_SYSTEM: u64 = 50_000_000;
+}
+
+impl Default for ExecutionBudget {
+    fn default() -> Self {
+        Self {
+            per_system: Self::DEFAULT_FUEL_PER_SYSTEM,
+            per_schedule: None,
+            suspend_on_exhaustion: true,
+        }
+    }
+}
+
+/// The execution budgets of mods, see [`crate::prelude::ModLoaderPlugin`]
+#[derive(Debug, Clone, Default)]
+pub struct ModBudgets {
+    /// The budget of mods without one of their own
+    pub default: ExecutionBudget,
+    /// Budgets of single mods, keyed by package name
+    mods: HashMap<String, ExecutionBudget>,
+}
+
+impl ModBudgets {
+    pub fn new(default: ExecutionBudget) -> Self {
+        Self {
+            default,
+            mods: HashMap::default(),
+        }
+    }
+
+    /// Gives the mod with the given package name its own budget
+    pub fn with_budget(mut self, name: impl Into<String>, budget: ExecutionBudget) -> Self {
+        self.set(name, budget);
+        self
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, budget: ExecutionBudget) {
+        self.mods.insert(name.into(), budget);
+    }
+
+    pub fn get(&self, name: &str) -> ExecutionBudget {
+        self.mods.get(name).copied().unwrap_or(self.default)
+    }
+}
+
+/// The error of a mod system interrupted for going over its budget
+#[derive(Debug)]
+pub struct BudgetExceeded {
+    /// The fuel the system was allowed to consume
+    pub fuel: u64,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "System went over its execution budget of {} fuel",
+            self.fuel
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// The error of a mod system skipped because its schedule went over budget
+#[derive(Debug)]
+pub struct ScheduleBudgetExceeded {
+    /// The fuel the schedule was allowed to consume
+    pub fuel: u64,
+}
+
+impl fmt::Display for ScheduleBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "System skipped, its schedule went over the execution budget of {} fuel",
+            self.fuel
+        )
+    }
+}
+
+impl std::error::Error for ScheduleBudgetExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budgets_fall_back_to_default() {
+        let budget = ExecutionBudget {
+            per_system: 1000,
+            per_schedule: Some(5000),
+            suspend_on_exhaustion: false,
+        };
+        let budgets = ModBudgets::default().with_budget("heavy", budget);
+
+        assert_eq!(budgets.get("heavy"), budget);
+        assert_eq!(budgets.get("other"), ExecutionBudget::default());
+    }
+}
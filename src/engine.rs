@@ -1,33 +1,239 @@
-use std::fmt;
+use std::{
+    fmt, fs,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use anyhow::*;
+use common::FileHash;
+use sha2::{Digest, Sha256};
+use tracing::warn;
 
-#[derive(Clone)]
-pub(crate) struct Engine(wasmtime::Engine);
+/// How mod instances get their memories and tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllocationStrategy {
+    /// Allocate everything when a mod is instantiated
+    OnDemand,
+    /// Preallocate slots for a fixed number of instances, which makes instantiating fast
+    ///
+    /// See <https://docs.wasmtime.dev/examples-fast-instantiation.html>
+    Pooling(PoolingSettings),
+}
+
+impl Default for AllocationStrategy {
+    fn default() -> Self {
+        Self::Pooling(PoolingSettings::default())
+    }
+}
+
+/// Limits of the pooling allocator, shared by every loaded mod
+///
+/// Mods import one memory per resource, so `total_memories` has to cover the resources of
+/// every mod loaded at once. Resources sit at the top of the 32-bit address space of their
+/// memory, so `max_memory_size` has to cover all of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolingSettings {
+    pub total_memories: u32,
+    /// The maximum size of a single memory, in bytes
+    ///
+    /// Every memory slot reserves this much address space, but only the pages holding values
+    /// are backed by memory. Capped to the address space of the host
+    pub max_memory_size: u64,
+    pub total_tables: u32,
+    pub table_elements: usize,
+    pub total_core_instances: u32,
+}
+
+impl Default for PoolingSettings {
+    fn default() -> Self {
+        Self {
+            total_memories: 100,
+            // The whole 32-bit address space, which is 4 GiB
+            max_memory_size: 1 << 32,
+            total_tables: 100,
+            table_elements: 5000,
+            total_core_instances: 100,
+        }
+    }
+}
+
+/// How much the compiler optimizes mods
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptLevel {
+    None,
+    #[default]
+    Speed,
+    SpeedAndSize,
+}
+
+/// Settings of the wasmtime engine that compiles and runs mods, see
+/// [`crate::prelude::ModLoaderPlugin`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineSettings {
+    /// Cache compiled mods on disk, using wasmtime's default cache config
+    pub cache: bool,
+    pub opt_level: OptLevel,
+    pub allocation: AllocationStrategy,
+    /// A directory to store mods in once compiled, so later loads skip compiling them
+    ///
+    /// Entries are keyed by the hash of the mod's wasm and of the engine's config, so they are
+    /// ignored once wasmtime or these settings change. Stale entries are never removed, so
+    /// several engines can share the directory. Games can ship a cache filled by a release
+    /// build to avoid compiling mods on first launch.
+    ///
+    /// Precompiled modules are loaded without validation, so the directory must only be
+    /// writable by the game
+    pub module_cache: Option<PathBuf>,
+}
 
-impl Default for Engine {
+impl Default for EngineSettings {
     fn default() -> Self {
+        Self {
+            cache: true,
+            opt_level: OptLevel::default(),
+            allocation: AllocationStrategy::default(),
+            module_cache: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Engine {
+    engine: wasmtime::Engine,
+    module_cache: Option<ModuleCache>,
+}
+
+impl Engine {
+    pub fn new(settings: &EngineSettings) -> Result<Self> {
         let mut config = wasmtime::Config::new();
 
-        config
-            .cache_config_load_default()
-            .expect("Failed to load cache config");
+        if settings.cache {
+            config
+                .cache_config_load_default()
+                .context("Failed to load cache config")?;
+        }
         config.parallel_compilation(true);
         config.wasm_custom_page_sizes(true);
+        // Enforces execution budgets, see `ExecutionBudget`
+        config.consume_fuel(true);
+
+        config.cranelift_opt_level(match settings.opt_level {
+            OptLevel::None => wasmtime::OptLevel::None,
+            OptLevel::Speed => wasmtime::OptLevel::Speed,
+            OptLevel::SpeedAndSize => wasmtime::OptLevel::SpeedAndSize,
+        });
+
+        let strategy = match &settings.allocation {
+            AllocationStrategy::OnDemand => wasmtime::InstanceAllocationStrategy::OnDemand,
+            AllocationStrategy::Pooling(settings) => {
+                let mut pool = wasmtime::PoolingAllocationConfig::new();
+                pool.total_memories(settings.total_memories);
+                pool.max_memory_size(
+                    usize::try_from(settings.max_memory_size).unwrap_or(usize::MAX),
+                );
+                pool.total_tables(settings.total_tables);
+                pool.table_elements(settings.table_elements);
+                pool.total_core_instances(settings.total_core_instances);
+                wasmtime::InstanceAllocationStrategy::Pooling(pool)
+            }
+        };
+        config.allocation_strategy(strategy);
+
+        let engine = wasmtime::Engine::new(&config).context("Invalid engine settings")?;
+
+        let module_cache = settings.module_cache.as_ref().map(|directory| {
+            let mut hasher = Sha256Hasher::default();
+            engine.precompile_compatibility_hash().hash(&mut hasher);
+            ModuleCache {
+                directory: directory.clone(),
+                engine_hash: FileHash::from_sha256(hasher.0.finalize().into()),
+            }
+        });
+
+        Ok(Self {
+            engine,
+            module_cache,
+        })
+    }
+}
+
+impl Deref for Engine {
+    type Target = wasmtime::Engine;
+
+    fn deref(&self) -> &Self::Target {
+        &self.engine
+    }
+}
+
+/// Modules precompiled by an engine, see [`EngineSettings::module_cache`]
+#[derive(Clone)]
+struct ModuleCache {
+    directory: PathBuf,
+    /// Changes whenever the version or config of wasmtime does
+    engine_hash: FileHash,
+}
+
+impl ModuleCache {
+    fn path(&self, wasm_hash: &FileHash) -> PathBuf {
+        self.directory
+            .join(format!("{}-{}.cwasm", wasm_hash, self.engine_hash))
+    }
+
+    fn load(&self, engine: &wasmtime::Engine, wasm_hash: &FileHash) -> Option<wasmtime::Module> {
+        let path = self.path(wasm_hash);
+        if !path.exists() {
+            return None;
+        }
+
+        // SAFETY: the cache directory is trusted, see `EngineSettings::module_cache`
+        match unsafe { wasmtime::Module::deserialize_file(engine, &path) } {
+            Result::Ok(module) => Some(module),
+            Err(err) => {
+                warn!("Ignoring invalid precompiled module {:?}:\n{:?}", path, err);
+                None
+            }
+        }
+    }
+
+    /// Writes a compiled module, leaving those compiled by other engines in place
+    fn store(&self, wasm_hash: &FileHash, module: &wasmtime::Module) -> Result<()> {
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+
+        fs::create_dir_all(&self.directory)?;
+
+        // Written under a name no other write uses first, so concurrent loads of the same mod
+        // never see or clobber partial files
+        let path = self.path(wasm_hash);
+        let temporary = path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temporary, module.serialize()?)?;
+        if let Err(err) = fs::rename(&temporary, &path) {
+            let _ = fs::remove_file(&temporary);
+            return Err(err.into());
+        }
 
-        // Enable pooling
-        // https://docs.wasmtime.dev/examples-fast-instantiation.html
-        let mut pool = wasmtime::PoolingAllocationConfig::new();
-        pool.total_memories(100);
-        pool.max_memory_size(1 << 31); // 2 GiB
-        pool.total_tables(100);
-        pool.table_elements(5000);
-        pool.total_core_instances(100);
-        config.allocation_strategy(wasmtime::InstanceAllocationStrategy::Pooling(pool));
+        Ok(())
+    }
+}
 
-        let engine = wasmtime::Engine::new(&config).unwrap();
+/// Feeds hashed values into SHA-256, whose output is the same across builds unlike
+/// [`std::hash::DefaultHasher`]
+#[derive(Default)]
+struct Sha256Hasher(Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
 
-        Self(engine)
+    fn finish(&self) -> u64 {
+        let digest = self.0.clone().finalize();
+        u64::from_le_bytes(digest[..8].try_into().unwrap())
     }
 }
 
@@ -41,8 +247,88 @@ impl fmt::Debug for Module {
 }
 
 impl Module {
-    pub fn new(engine: &Engine, bytes: impl AsRef<[u8]>) -> Result<Self> {
-        let module = wasmtime::Module::new(&engine.0, bytes)?;
+    /// Compiles a module, unless the engine's module cache already holds it
+    pub fn new(engine: &Engine, wasm_hash: &FileHash, bytes: impl AsRef<[u8]>) -> Result<Self> {
+        let Some(cache) = &engine.module_cache else {
+            return Ok(Self(wasmtime::Module::new(&engine.engine, bytes)?));
+        };
+
+        if let Some(module) = cache.load(&engine.engine, wasm_hash) {
+            return Ok(Self(module));
+        }
+
+        let module = wasmtime::Module::new(&engine.engine, bytes)?;
+        if let Err(err) = cache.store(wasm_hash, &module) {
+            warn!("Failed to cache precompiled module:\n{:?}", err);
+        }
         Ok(Self(module))
     }
 }
+
+impl Deref for Module {
+    type Target = wasmtime::Module;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_settings_return_error() {
+        let settings = EngineSettings {
+            cache: false,
+            allocation: AllocationStrategy::Pooling(PoolingSettings {
+                // Far more address space than any machine has
+                total_memories: u32::MAX,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(Engine::new(&settings).is_err());
+
+        let settings = EngineSettings {
+            cache: false,
+            allocation: AllocationStrategy::OnDemand,
+            ..Default::default()
+        };
+        assert!(Engine::new(&settings).is_ok());
+    }
+
+    #[test]
+    fn module_cache_stores_compiled_modules() {
+        let directory = std::env::temp_dir().join(format!(
+            "bevy_harmonize_module_cache_{}",
+            std::process::id()
+        ));
+        let settings = EngineSettings {
+            cache: false,
+            allocation: AllocationStrategy::OnDemand,
+            module_cache: Some(directory.clone()),
+            ..Default::default()
+        };
+        let engine = Engine::new(&settings).unwrap();
+        let wasm_hash = FileHash::from_sha256([1; 32]);
+        let cache = engine.module_cache.clone().unwrap();
+
+        Module::new(&engine, &wasm_hash, "(module)").unwrap();
+        assert!(cache.path(&wasm_hash).exists());
+        assert!(cache.load(&engine, &wasm_hash).is_some());
+
+        // Entries of other engines are kept
+        let other = Engine::new(&EngineSettings {
+            opt_level: OptLevel::None,
+            ..settings
+        })
+        .unwrap();
+        Module::new(&other, &wasm_hash, "(module)").unwrap();
+        assert!(cache.path(&wasm_hash).exists());
+        assert!(cache.load(&engine, &wasm_hash).is_some());
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+use bevy_ecs::event::EventWriter;
+use bevy_ecs_macros::{Event, SystemParam};
+use common::ManifestDiff;
+
+use crate::{loaded::MigrationReport, mods::ModHandle};
+
+/// Sent when a mod is loaded for the first time, after its Start systems ran
+#[derive(Event, Debug, Clone)]
+pub struct ModLoaded {
+    pub handle: ModHandle,
+}
+
+/// Sent when a mod fails to load or reload
+///
+/// A reload also fails when the state of the running version can't be migrated to the new
+/// one. The running version then keeps running, see [`crate::prelude::ModStatus`]
+#[derive(Event, Debug, Clone)]
+pub struct ModLoadFailed {
+    pub handle: ModHandle,
+    pub error: String,
+}
+
+/// Sent when a mod is unloaded, once its entities are despawned
+#[derive(Event, Debug, Clone)]
+pub struct ModUnloaded {
+    pub handle: ModHandle,
+}
+
+/// Sent when a new version of a mod replaces the previous one, once its state was migrated
+#[derive(Event, Debug, Clone)]
+pub struct ModReloaded {
+    pub handle: ModHandle,
+    /// How resource values were carried over from the previous version
+    pub report: MigrationReport,
+    /// What changed from the previous version, and whether it breaks anything
+    pub changes: ManifestDiff,
+}
+
+/// Sent when a mod system traps, for instance when it panics
+#[derive(Event, Debug, Clone)]
+pub struct ModTrapped {
+    pub handle: ModHandle,
+    /// Name of the system that trapped
+    pub system: String,
+    pub message: String,
+}
+
+/// Sent when a mod is suspended after one of its systems went over its execution budget
+#[derive(Event, Debug, Clone)]
+pub struct ModSuspended {
+    pub handle: ModHandle,
+    /// Name of the system that went over budget
+    pub system: String,
+}
+
+/// Writers for every mod lifecycle event
+#[derive(SystemParam)]
+pub(crate) struct ModEventWriters<'w> {
+    pub loaded: EventWriter<'w, ModLoaded>,
+    pub load_failed: EventWriter<'w, ModLoadFailed>,
+    pub unloaded: EventWriter<'w, ModUnloaded>,
+    pub reloaded: EventWriter<'w, ModReloaded>,
+    pub trapped: EventWriter<'w, ModTrapped>,
+    pub suspended: EventWriter<'w, ModSuspended>,
+}
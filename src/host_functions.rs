@@ -0,0 +1,86 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::*;
+use bevy_platform::collections::HashMap;
+use wasmtime::{FuncType, Linker, Val, ValType};
+
+/// Name of the import module mods import host functions from
+pub const HOST_FUNCTION_MODULE: &str = "bevy_harmonize_host";
+
+type HostFunctionImpl = dyn Fn(&[Val], &mut [Val]) -> Result<()> + Send + Sync;
+
+/// Functions the host lets mods call, see [`crate::prelude::ModLoaderPlugin`]
+///
+/// Mods import them by name from the `bevy_harmonize_host` module, and are only loaded once
+/// granted [`common::Capability::HostFunction`] with that name. A function returning an error
+/// traps the mod system calling it
+#[derive(Clone, Default)]
+pub struct HostFunctions(HashMap<String, HostFunction>);
+
+#[derive(Clone)]
+struct HostFunction {
+    params: Vec<ValType>,
+    results: Vec<ValType>,
+    func: Arc<HostFunctionImpl>,
+}
+
+impl HostFunctions {
+    /// Registers a function taking and returning values of the given types
+    pub fn with_function(
+        mut self,
+        name: impl Into<String>,
+        params: impl IntoIterator<Item = ValType>,
+        results: impl IntoIterator<Item = ValType>,
+        func: impl Fn(&[Val], &mut [Val]) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.register(name, params, results, func);
+        self
+    }
+
+    /// Registers a function, replacing any function with the same name
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        params: impl IntoIterator<Item = ValType>,
+        results: impl IntoIterator<Item = ValType>,
+        func: impl Fn(&[Val], &mut [Val]) -> Result<()> + Send + Sync + 'static,
+    ) {
+        let function = HostFunction {
+            params: params.into_iter().collect(),
+            results: results.into_iter().collect(),
+            func: Arc::new(func),
+        };
+        self.0.insert(name.into(), function);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Defines the function with the given name in the host function module
+    pub(crate) fn define<T>(&self, linker: &mut Linker<T>, name: &str) -> Result<()> {
+        let function = self.0.get(name).ok_or_else(|| {
+            anyhow!(
+                "Mod imports host function {:?}, which the host did not register",
+                name
+            )
+        })?;
+
+        let ty = FuncType::new(
+            linker.engine(),
+            function.params.iter().cloned(),
+            function.results.iter().cloned(),
+        );
+        let func = function.func.clone();
+        linker.func_new(HOST_FUNCTION_MODULE, name, ty, move |_, params, results| {
+            func(params, results)
+        })?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for HostFunctions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
@@ -1,7 +1,30 @@
+pub(crate) mod asset;
+pub(crate) mod budget;
 pub(crate) mod engine;
+pub(crate) mod events;
+pub(crate) mod host_functions;
 pub(crate) mod loaded;
 pub(crate) mod mods;
+pub(crate) mod permissions;
+pub(crate) mod schedules;
+pub(crate) mod signing;
+pub(crate) mod systems;
 
 pub mod prelude {
-    pub use crate::mods::{ModLoaderPlugin, Mods};
+    pub use common::Capability;
+    pub use wasmtime::{Val, ValType};
+
+    pub use crate::{
+        asset::{ModAsset, ModAssetLoader},
+        budget::{BudgetExceeded, ExecutionBudget, ModBudgets, ScheduleBudgetExceeded},
+        engine::{AllocationStrategy, EngineSettings, OptLevel, PoolingSettings},
+        events::{ModLoadFailed, ModLoaded, ModReloaded, ModSuspended, ModTrapped, ModUnloaded},
+        host_functions::HostFunctions,
+        loaded::{LoadedFeature, LoadedMod, MigrationReport, ModPanic, API_VERSION},
+        mods::{ModHandle, ModLoaderPlugin, ModStatus, Mods},
+        permissions::ModPermissions,
+        schedules::ModSchedules,
+        signing::{SignaturePolicy, TrustStore},
+        systems::{mod_resource_id, ExecutionMode},
+    };
 }
@@ -2,19 +2,25 @@ use anyhow::Result;
 use bevy_platform::collections::HashMap;
 
 use super::schedule::LoadedSchedules;
+use crate::schedules::ModSchedules;
 
-// These fields are read by a debug macro
-#[allow(dead_code)]
 #[derive(Debug)]
 pub struct LoadedFeature {
-    pub name: String,
-    pub resources: HashMap<common::StableId, Vec<u8>>,
-    pub schedules: LoadedSchedules,
+    pub(crate) name: String,
+    /// The default value of each resource of the feature, serialized with bevy_reflect
+    pub(crate) resources: HashMap<common::StableId, Vec<u8>>,
+    pub(crate) schedules: LoadedSchedules,
+    /// Whether the systems of the feature run, see [`crate::prelude::Mods::set_feature_enabled`]
+    pub(crate) enabled: bool,
 }
 
 impl LoadedFeature {
-    pub fn try_from_descriptor(descriptor: &common::FeatureDescriptor) -> Result<Self> {
-        let schedules = LoadedSchedules::try_from_schedule_descriptors(&descriptor.schedules)?;
+    pub(crate) fn try_from_descriptor(
+        descriptor: &common::FeatureDescriptor,
+        schedules: &ModSchedules,
+    ) -> Result<Self> {
+        let schedules =
+            LoadedSchedules::try_from_schedule_descriptors(&descriptor.schedules, schedules)?;
 
         Ok(Self {
             name: descriptor.name.to_owned(),
@@ -24,6 +30,34 @@ impl LoadedFeature {
                 .map(|(id, bytes)| (id.to_owned(), bytes.to_owned()))
                 .collect(),
             schedules,
+            enabled: true,
         })
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The resources of the feature, with their default value serialized with bevy_reflect
+    pub fn resources(&self) -> &HashMap<common::StableId, Vec<u8>> {
+        &self.resources
+    }
+
+    /// The schedules the feature has systems in
+    pub fn schedules(&self) -> impl Iterator<Item = &common::StableId> {
+        self.schedules.ids()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Every system of the feature, across all of its schedules
+    pub(crate) fn system_ids(&self) -> impl Iterator<Item = &common::SystemId> {
+        self.schedules
+            .ids()
+            .filter_map(|id| self.schedules.get(id))
+            .flat_map(|schedule| schedule.ordered_systems())
+            .map(|(id, _)| id)
+    }
 }
@@ -0,0 +1,204 @@
+use std::fmt;
+
+use anyhow::*;
+use bevy_ecs::{
+    change_detection::CHECK_TICK_THRESHOLD, component::Tick, entity::Entity, system::Commands,
+};
+use bevy_platform::collections::HashMap;
+use common::{Capability, RawWasmVec};
+use wasmtime::{Caller, Linker};
+
+/// Name of the import module declared by `bevy_harmonize_api`
+pub const IMPORT_MODULE: &str = "bevy_harmonize";
+
+/// Data owned by a mod's store, accessible to host functions while the mod runs
+#[derive(Default)]
+pub(crate) struct HostState {
+    panic: Option<RawWasmVec>,
+
+    /// The tick of the current system run. Every run advances it, so changes made by a system
+    /// are seen by the systems running after it, even within a single run of a schedule
+    change_tick: Tick,
+
+    /// The tick of the last run of the current system
+    last_run: Tick,
+
+    /// Whether the current system is a run condition, which may not change resources or spawn
+    /// entities
+    read_only: bool,
+
+    /// Last tick at which each system ran, indexed by export index
+    last_runs: HashMap<u32, Tick>,
+
+    /// Last tick at which each component was flagged as changed, indexed by component id
+    changed: Vec<Tick>,
+
+    /// Components flagged as changed by the current system, not yet reported to the world
+    flagged: Vec<usize>,
+
+    /// Entities spawned by the mod. The index of an entity is the handle given to the mod
+    entities: Vec<Entity>,
+
+    /// Number of entities requested by the mod, not yet spawned in the world
+    pending_spawns: u32,
+}
+
+impl HostState {
+    pub fn new(component_count: usize) -> Self {
+        Self {
+            changed: vec![Tick::new(0); component_count],
+            ..Default::default()
+        }
+    }
+
+    /// Prepares state for the system exported under the given index, about to run
+    pub fn begin_system(&mut self, index: u32, read_only: bool) {
+        self.panic = None;
+        self.read_only = read_only;
+        self.change_tick = Tick::new(self.change_tick.get().wrapping_add(1));
+        if self.change_tick.get().is_multiple_of(CHECK_TICK_THRESHOLD) {
+            self.check_ticks();
+        }
+        self.last_run = self
+            .last_runs
+            .insert(index, self.change_tick)
+            .unwrap_or(Tick::new(0));
+    }
+
+    /// Clamps ticks so old they would look newer than the current one once it wraps around
+    fn check_ticks(&mut self) {
+        let change_tick = self.change_tick.get();
+        for tick in self.last_runs.values_mut().chain(self.changed.iter_mut()) {
+            if change_tick.wrapping_sub(tick.get()) > Tick::MAX.get() {
+                *tick = Tick::new(change_tick.wrapping_sub(Tick::MAX.get()));
+            }
+        }
+    }
+
+    /// Spawns entities requested by the mod since the last flush
+    pub fn flush(&mut self, commands: &mut Commands) {
+        for _ in 0..self.pending_spawns {
+            let entity = commands.spawn_empty().id();
+            self.entities.push(entity);
+        }
+        self.pending_spawns = 0;
+    }
+
+    pub fn take_entities(&mut self) -> Vec<Entity> {
+        std::mem::take(&mut self.entities)
+    }
+
+    pub fn set_entities(&mut self, entities: Vec<Entity>) {
+        self.entities = entities;
+    }
+
+    pub fn take_panic(&mut self) -> Option<RawWasmVec> {
+        self.panic.take()
+    }
+
+    /// Takes the ids of the components flagged as changed since the last call
+    pub fn take_flagged(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.flagged)
+    }
+
+    /// Flags the component as changed by the current system
+    pub fn flag_changed(&mut self, component_id: usize) -> Result<()> {
+        if self.read_only {
+            bail!("Run conditions can't change resources");
+        }
+        let tick = self
+            .changed
+            .get_mut(component_id)
+            .ok_or_else(|| anyhow!("Unknown component id {}", component_id))?;
+        *tick = self.change_tick;
+        if !self.flagged.contains(&component_id) {
+            self.flagged.push(component_id);
+        }
+        Ok(())
+    }
+
+    /// Whether the component was flagged as changed since the current system last ran
+    pub fn is_changed(&self, component_id: usize) -> bool {
+        self.changed
+            .get(component_id)
+            .is_some_and(|tick| tick.is_newer_than(self.last_run, self.change_tick))
+    }
+}
+
+/// A panic raised inside of a mod
+#[derive(Debug)]
+pub struct ModPanic {
+    /// The panic message, as read out of the mod's memory
+    pub message: String,
+}
+
+impl fmt::Display for ModPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Panic in wasm module.\n{}", self.message)
+    }
+}
+
+impl std::error::Error for ModPanic {}
+
+/// Returns the capability a mod must be granted to import a host function
+pub(crate) fn required_capability(import: &str) -> Option<Capability> {
+    match import {
+        "spawn_empty" => Some(Capability::SpawnEntities),
+        _ => None,
+    }
+}
+
+/// Defines the functions in the `bevy_harmonize` import module, leaving out those gated
+/// behind a capability that wasn't granted
+pub(crate) fn define_imports(linker: &mut Linker<HostState>, granted: &[Capability]) -> Result<()> {
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "panic",
+        |mut caller: Caller<HostState>, ptr: u32, len: u32| -> Result<()> {
+            let ptr = ptr as usize;
+            let len = len as usize;
+
+            caller.data_mut().panic = Some(RawWasmVec { ptr, len });
+
+            // Trap
+            Err(anyhow!("Panic in wasm module"))
+        },
+    )?;
+
+    if granted.contains(&Capability::SpawnEntities) {
+        linker.func_wrap(
+            IMPORT_MODULE,
+            "spawn_empty",
+            |mut caller: Caller<HostState>| -> Result<u32> {
+                let state = caller.data_mut();
+                if state.read_only {
+                    bail!("Run conditions can't spawn entities");
+                }
+
+                // The entity is spawned once the system returns, but its handle is known right away
+                let handle = state.entities.len() as u32 + state.pending_spawns;
+                state.pending_spawns += 1;
+
+                Ok(handle)
+            },
+        )?;
+    }
+
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "flag_component_changed",
+        |mut caller: Caller<HostState>, component_id: u32| -> Result<()> {
+            caller.data_mut().flag_changed(component_id as usize)
+        },
+    )?;
+
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "is_component_changed",
+        |caller: Caller<HostState>, component_id: u32| -> Result<u32> {
+            Ok(caller.data().is_changed(component_id as usize) as u32)
+        },
+    )?;
+
+    Ok(())
+}
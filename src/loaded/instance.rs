@@ -0,0 +1,397 @@
+use std::{fmt, ops::Range};
+
+use anyhow::*;
+use bevy_ecs::{entity::Entity, system::Commands};
+use bevy_platform::collections::{HashMap, HashSet};
+use common::{Capability, StableId};
+use wasmtime::{ExternType, Linker, Memory, MemoryType, Store, Trap, TypedFunc};
+
+use super::{
+    host::{define_imports, required_capability, HostState, ModPanic, IMPORT_MODULE},
+    resource::{InitialMemory, MEMORY_IMPORT_MODULE},
+};
+use crate::{
+    budget::{BudgetExceeded, ExecutionBudget},
+    engine::{Engine, Module},
+    host_functions::{HostFunctions, HOST_FUNCTION_MODULE},
+    systems::mark_resource_changed,
+};
+
+/// An instantiated mod, ready to have its systems run
+pub(crate) struct Instance {
+    store: Store<HostState>,
+    instance: wasmtime::Instance,
+    run: TypedFunc<u32, u32>,
+    /// Memories holding the value of each component, indexed by component id, and where in
+    /// them the value is
+    ///
+    /// These live as long as the store, so values persist between system runs
+    components: Vec<(StableId, Range<usize>, Memory)>,
+}
+
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instance").finish_non_exhaustive()
+    }
+}
+
+impl Instance {
+    pub fn new(
+        engine: &Engine,
+        module: &Module,
+        mut memories: HashMap<String, InitialMemory>,
+        granted: &[Capability],
+        host_functions: &HostFunctions,
+        budget: ExecutionBudget,
+    ) -> Result<Self> {
+        // Each imported memory holds one component, in component id order
+        let component_count = module
+            .imports()
+            .filter(|import| matches!(import.ty(), ExternType::Memory(_)))
+            .count();
+
+        let mut store = Store::new(engine, HostState::new(component_count));
+        let mut linker = Linker::new(engine);
+
+        define_imports(&mut linker, granted)?;
+
+        let mut components = Vec::with_capacity(component_count);
+        for import in module.imports() {
+            if !matches!(import.ty(), ExternType::Memory(_)) {
+                continue;
+            }
+            if import.module() != MEMORY_IMPORT_MODULE {
+                bail!(
+                    "Mod imports memory {:?} from unknown module {:?}",
+                    import.name(),
+                    import.module()
+                );
+            }
+
+            let InitialMemory { id, address, bytes } = memories
+                .remove(import.name())
+                .ok_or_else(|| anyhow!("Mod imports memory for unknown type {}", import.name()))?;
+
+            // Pages of a single byte, so the memory ends right after the value. Pages below the
+            // value are never touched, so they take no physical memory
+            let ty = MemoryType::builder()
+                .page_size_log2(0)
+                .min(address.end as u64)
+                .build()?;
+            let memory = Memory::new(&mut store, ty)?;
+            memory.write(&mut store, address.start, &bytes)?;
+
+            linker.define(&store, import.module(), import.name(), memory)?;
+            components.push((id, address, memory));
+        }
+
+        // Otherwise gated imports would only trap once called
+        let mut defined = HashSet::new();
+        for import in module.imports() {
+            let capability = match import.module() {
+                IMPORT_MODULE => required_capability(import.name()),
+                HOST_FUNCTION_MODULE => Some(Capability::HostFunction(import.name().to_owned())),
+                _ => None,
+            };
+            if let Some(capability) = capability {
+                if !granted.contains(&capability) {
+                    bail!(
+                        "Mod imports {:?}, which requires the {} capability",
+                        import.name(),
+                        capability
+                    );
+                }
+            }
+
+            if import.module() == HOST_FUNCTION_MODULE && defined.insert(import.name()) {
+                host_functions.define(&mut linker, import.name())?;
+            }
+        }
+
+        linker.define_unknown_imports_as_traps(module)?;
+
+        // Instantiating may run the module's start function, which gets the budget of a system
+        store.set_fuel(budget.per_system)?;
+        let instance = linker
+            .instantiate(&mut store, module)
+            .with_context(|| "Error instantiating wasm module")?;
+
+        let run = instance
+            .get_typed_func::<u32, u32>(&mut store, "run")
+            .with_context(|| "Mod does not export a valid run function")?;
+
+        Ok(Self {
+            store,
+            instance,
+            run,
+            components,
+        })
+    }
+
+    /// Returns the bytes of the value of the given type
+    pub fn memory(&self, id: &StableId) -> Option<&[u8]> {
+        let (_, address, memory) = self.components.iter().find(|(other, ..)| other == id)?;
+        Some(&memory.data(&self.store)[address.clone()])
+    }
+
+    pub fn memory_mut(&mut self, id: &StableId) -> Option<&mut [u8]> {
+        let (_, address, memory) = self.components.iter().find(|(other, ..)| other == id)?;
+        Some(&mut memory.data_mut(&mut self.store)[address.clone()])
+    }
+
+    /// Takes over the entities spawned by another instance, keeping their handles
+    pub fn inherit_entities(&mut self, previous: &mut Instance) {
+        let entities = previous.take_entities();
+        self.store.data_mut().set_entities(entities);
+    }
+
+    pub fn take_entities(&mut self) -> Vec<Entity> {
+        self.store.data_mut().take_entities()
+    }
+
+    /// Runs the system exported under the given index, interrupting it once it consumed the
+    /// given fuel
+    ///
+    /// Entities spawned by the system are spawned with the given commands once it returns, and
+    /// the resources it flagged as changed are marked as changed in the world. Read only
+    /// systems trap instead of doing either
+    pub fn run_system(
+        &mut self,
+        index: u32,
+        read_only: bool,
+        commands: &mut Commands,
+        fuel: u64,
+    ) -> Result<SystemRun> {
+        self.store.data_mut().begin_system(index, read_only);
+        self.store.set_fuel(fuel)?;
+
+        let result = self.run.call(&mut self.store, index).map_err(|err| {
+            if err.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel) {
+                return Error::new(BudgetExceeded { fuel });
+            }
+
+            match self.store.data_mut().take_panic() {
+                Some(panic) => {
+                    let memory = self.instance.get_memory(&mut self.store, "memory").unwrap();
+                    let message = memory
+                        .data(&self.store)
+                        .get(panic.into_range())
+                        .map(String::from_utf8_lossy)
+                        .unwrap_or_default()
+                        .into_owned();
+                    Error::new(ModPanic { message })
+                }
+                None => err,
+            }
+        });
+
+        self.store.data_mut().flush(commands);
+        for component_id in self.store.data_mut().take_flagged() {
+            let (id, ..) = &self.components[component_id];
+            commands.queue(mark_resource_changed(id.clone()));
+        }
+
+        let consumed = fuel - self.store.get_fuel()?;
+        result.map(|output| SystemRun {
+            consumed,
+            output: output != 0,
+        })
+    }
+}
+
+/// What a system that ran to completion left behind
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SystemRun {
+    /// The fuel consumed by the system
+    pub consumed: u64,
+    /// The value returned by run conditions, always `false` for other systems
+    pub output: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    };
+
+    use bevy_ecs::world::{CommandQueue, World};
+    use common::{
+        FeatureDescriptor, FieldLayout, FieldSignature, FileHash, ModManifest, TypeSignature,
+    };
+    use wasmtime::{Val, ValType};
+
+    use super::*;
+    use crate::{engine::EngineSettings, loaded::resource::initial_memories};
+
+    fn engine() -> Engine {
+        let settings = EngineSettings {
+            cache: false,
+            ..Default::default()
+        };
+        Engine::new(&settings).unwrap()
+    }
+
+    /// A manifest with a single `Counter { count: 5 }` resource
+    fn counter_manifest(id: &StableId) -> ModManifest {
+        ModManifest {
+            wasm_hash: FileHash::empty(),
+            version: "0.1.0".to_owned(),
+            api_version: crate::loaded::API_VERSION.to_owned(),
+            common_version: common::VERSION.to_owned(),
+            types: vec![TypeSignature::Struct {
+                ty: id.clone(),
+                size: Some(4),
+                align: Some(4),
+                generics: vec![],
+                fields: vec![FieldSignature {
+                    name: "count".to_owned(),
+                    ty: StableId::new("unknown", "u32"),
+                }],
+                layout: vec![FieldLayout { offset: 0, size: 4 }],
+            }],
+            features: vec![FeatureDescriptor {
+                name: "test".to_owned(),
+                resources: vec![(id.clone(), vec![5])],
+                schedules: vec![],
+            }],
+            capabilities: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    fn instantiate(engine: &Engine, manifest: &ModManifest, wat: &str) -> Instance {
+        let module = Module::new(engine, &manifest.wasm_hash, wat).unwrap();
+        let memories = initial_memories(manifest).unwrap();
+        Instance::new(
+            engine,
+            &module,
+            memories,
+            &[],
+            &HostFunctions::default(),
+            ExecutionBudget::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn systems_load_and_store_resources() {
+        let id = StableId::new("test", "Counter");
+        let manifest = counter_manifest(&id);
+
+        // What the build leaves of `counter.count += 1`, accessing the value at its address
+        let (_, address) = common::type_addresses(&manifest.types).unwrap()[0].clone();
+        let wat = format!(
+            r#"(module
+                (import "bevy" "test::Counter" (memory $counter 0 (pagesize 1)))
+                (memory (export "memory") 1)
+                (func (export "run") (param i32) (result i32)
+                    i32.const {address}
+                    i32.const {address}
+                    i32.load $counter
+                    i32.const 1
+                    i32.add
+                    i32.store $counter
+                    i32.const 0))"#,
+            address = address.start
+        );
+
+        let mut instance = instantiate(&engine(), &manifest, &wat);
+        assert_eq!(instance.memory(&id), Some(&5u32.to_le_bytes()[..]));
+
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        for _ in 0..2 {
+            instance.run_system(0, false, &mut commands, 1000).unwrap();
+        }
+        assert_eq!(instance.memory(&id), Some(&7u32.to_le_bytes()[..]));
+    }
+
+    #[test]
+    fn changes_are_seen_by_systems_that_ran_before() {
+        let manifest = counter_manifest(&StableId::new("test", "Counter"));
+        // A `resource_changed::<Counter>` condition under index 0, and a system changing the
+        // counter under index 1
+        let wat = r#"(module
+            (import "bevy_harmonize" "is_component_changed" (func $is_changed (param i32) (result i32)))
+            (import "bevy_harmonize" "flag_component_changed" (func $flag (param i32)))
+            (import "bevy" "test::Counter" (memory $counter 0 (pagesize 1)))
+            (memory (export "memory") 1)
+            (func (export "run") (param i32) (result i32)
+                local.get 0
+                if (result i32)
+                    i32.const 0
+                    call $flag
+                    i32.const 0
+                else
+                    i32.const 0
+                    call $is_changed
+                end))"#;
+        let mut instance = instantiate(&engine(), &manifest, wat);
+
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut run = |index, read_only| instance.run_system(index, read_only, &mut commands, 1000);
+
+        // Both run within a single run of a schedule
+        assert!(!run(0, true).unwrap().output);
+        run(1, false).unwrap();
+        // The next run of the schedule
+        assert!(run(0, true).unwrap().output);
+        assert!(!run(0, true).unwrap().output);
+
+        // Run conditions can't change anything
+        let err = run(1, true).unwrap_err();
+        assert!(format!("{:#}", err).contains("can't change resources"));
+    }
+
+    #[test]
+    fn host_functions_require_a_grant() {
+        let engine = engine();
+        let wat = r#"(module
+            (import "bevy_harmonize_host" "double" (func $double (param i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "run") (param i32) (result i32)
+                local.get 0
+                call $double))"#;
+        let module = Module::new(&engine, &FileHash::empty(), wat).unwrap();
+
+        let called = Arc::new(AtomicI32::new(0));
+        let host_functions =
+            HostFunctions::default().with_function("double", [ValType::I32], [ValType::I32], {
+                let called = called.clone();
+                move |params, results| {
+                    let value = params[0].unwrap_i32();
+                    called.store(value, Ordering::Relaxed);
+                    results[0] = Val::I32(value * 2);
+                    Ok(())
+                }
+            });
+        let granted = [Capability::HostFunction("double".to_owned())];
+
+        let instance = |granted: &[Capability], host_functions: &HostFunctions| {
+            Instance::new(
+                &engine,
+                &module,
+                HashMap::new(),
+                granted,
+                host_functions,
+                ExecutionBudget::default(),
+            )
+        };
+        let err = instance(&[], &host_functions).unwrap_err();
+        assert!(err.to_string().contains("HostFunction(\"double\")"));
+        let err = instance(&granted, &HostFunctions::default()).unwrap_err();
+        assert!(err.to_string().contains("did not register"));
+
+        let mut instance = instance(&granted, &host_functions).unwrap();
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let run = instance.run_system(3, false, &mut commands, 1000).unwrap();
+        assert!(run.output);
+        assert_eq!(called.load(Ordering::Relaxed), 3);
+    }
+}
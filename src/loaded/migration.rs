@@ -0,0 +1,425 @@
+use std::mem::discriminant;
+
+use anyhow::*;
+use common::{FieldLayout, StableId, TypeSignature, VariantSignature};
+
+use super::resource::{Layouts, Primitive};
+
+/// Describes how resource values were carried over when a mod was reloaded
+///
+/// Each entry is the path of a value, like `MyResource.position.0`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MigrationReport {
+    /// Values copied over from the previous version of the mod
+    pub migrated: Vec<String>,
+    /// Values that were added or changed type, and were reset to their default
+    pub defaulted: Vec<String>,
+    /// Values that no longer exist in the new version of the mod
+    pub dropped: Vec<String>,
+}
+
+/// Copies values laid out following the types of a previous version of a mod into
+/// memory laid out following the types of its new version
+///
+/// Fields are matched on both their name and type, so they may be freely reordered
+pub(crate) struct Migration<'a> {
+    old: Layouts<'a>,
+    new: Layouts<'a>,
+    pub report: MigrationReport,
+}
+
+impl<'a> Migration<'a> {
+    pub fn new(old_types: &'a [TypeSignature], new_types: &'a [TypeSignature]) -> Self {
+        Self {
+            old: Layouts::new(old_types),
+            new: Layouts::new(new_types),
+            report: MigrationReport::default(),
+        }
+    }
+
+    /// Migrates a value of the given type from `src` into `dest`
+    ///
+    /// `dest` must already hold the new default value, which is kept for anything
+    /// that can't be migrated
+    pub fn migrate(&mut self, id: &StableId, src: &[u8], dest: &mut [u8]) -> Result<()> {
+        self.migrate_value(id, id.name.clone(), src, dest)
+    }
+
+    fn migrate_value(
+        &mut self,
+        id: &StableId,
+        path: String,
+        src: &[u8],
+        dest: &mut [u8],
+    ) -> Result<()> {
+        if Primitive::from_id(id).is_some() {
+            if src.len() != dest.len() {
+                bail!("Expected {:?} to be {} bytes", id, dest.len());
+            }
+            dest.copy_from_slice(src);
+            self.report.migrated.push(path);
+            return Ok(());
+        }
+
+        let (Some(old), Some(new)) = (self.old.get(id), self.new.get(id)) else {
+            self.report.defaulted.push(path);
+            return Ok(());
+        };
+
+        // A struct turned into a tuple struct, or the other way around, is incompatible. The
+        // version of the crate declaring the type doesn't matter, only its signature does
+        if discriminant(old) != discriminant(new) {
+            self.report.defaulted.push(path);
+            return Ok(());
+        }
+
+        if let (Some(old_fields), Some(new_fields)) = (fields(old), fields(new)) {
+            return self.migrate_fields(id, &path, old_fields, src, new_fields, dest);
+        }
+
+        match (old, new) {
+            (
+                TypeSignature::Array {
+                    item_ty: old_item,
+                    capacity: old_capacity,
+                    ..
+                },
+                TypeSignature::Array {
+                    item_ty,
+                    capacity: new_capacity,
+                    ..
+                },
+            ) if old_item == item_ty => {
+                // Items are tightly packed, but their size may have changed between versions
+                let old_stride = src.len() / (*old_capacity).max(1);
+                let new_stride = dest.len() / (*new_capacity).max(1);
+
+                for index in 0..*new_capacity {
+                    let item_path = format!("{}[{}]", path, index);
+                    if index >= *old_capacity {
+                        self.report.defaulted.push(item_path);
+                        continue;
+                    }
+
+                    let src = &src[index * old_stride..(index + 1) * old_stride];
+                    let dest = &mut dest[index * new_stride..(index + 1) * new_stride];
+                    self.migrate_value(item_ty, item_path, src, dest)?;
+                }
+                for index in *new_capacity..*old_capacity {
+                    self.report.dropped.push(format!("{}[{}]", path, index));
+                }
+                Ok(())
+            }
+            // Values with no fields to match are copied as a whole, as long as neither they nor
+            // any type they are made of changed
+            _ if new
+                .size()
+                .is_some_and(|size| size == dest.len() && size == src.len())
+                && self.is_unchanged(id, &mut Vec::new()) =>
+            {
+                dest.copy_from_slice(src);
+                self.report.migrated.push(path);
+                Ok(())
+            }
+            _ => {
+                self.report.defaulted.push(path);
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether a type and every type it is made of have the same signature in both versions
+    ///
+    /// Collections never count as unchanged, since their values point into the memory of the
+    /// previous version of the mod
+    fn is_unchanged<'b>(&self, id: &'b StableId, visited: &mut Vec<&'b StableId>) -> bool
+    where
+        'a: 'b,
+    {
+        if Primitive::from_id(id).is_some() || visited.contains(&id) {
+            return true;
+        }
+        visited.push(id);
+
+        let (Some(old), Some(new)) = (self.old.get(id), self.new.get(id)) else {
+            return false;
+        };
+        if old != new {
+            return false;
+        }
+
+        match new {
+            TypeSignature::Struct { fields, .. } => fields
+                .iter()
+                .all(|field| self.is_unchanged(&field.ty, visited)),
+            TypeSignature::TupleStruct { fields, .. } | TypeSignature::Tuple { fields, .. } => {
+                fields.iter().all(|field| self.is_unchanged(field, visited))
+            }
+            TypeSignature::Array { item_ty, .. } => self.is_unchanged(item_ty, visited),
+            TypeSignature::Enum { variants, .. } => variants.iter().all(|variant| match variant {
+                VariantSignature::Struct { fields, .. } => fields
+                    .iter()
+                    .all(|field| self.is_unchanged(&field.ty, visited)),
+                VariantSignature::Tuple { fields, .. } => {
+                    fields.iter().all(|field| self.is_unchanged(field, visited))
+                }
+                VariantSignature::Unit { .. } => true,
+            }),
+            TypeSignature::Opaque { .. } => true,
+            TypeSignature::List { .. } | TypeSignature::Map { .. } | TypeSignature::Set { .. } => {
+                false
+            }
+        }
+    }
+
+    fn migrate_fields(
+        &mut self,
+        id: &StableId,
+        path: &str,
+        old_fields: Vec<Field>,
+        src: &[u8],
+        new_fields: Vec<Field>,
+        dest: &mut [u8],
+    ) -> Result<()> {
+        for new_field in new_fields.iter() {
+            let field_path = format!("{}.{}", path, new_field.name);
+            let old_field = old_fields
+                .iter()
+                .find(|old_field| old_field.name == new_field.name && old_field.ty == new_field.ty);
+
+            let Some(old_field) = old_field else {
+                self.report.defaulted.push(field_path);
+                continue;
+            };
+
+            let src = old_field
+                .bytes(src)
+                .ok_or_else(|| anyhow!("Field of {:?} is out of bounds", id))?;
+            let dest = new_field
+                .bytes_mut(dest)
+                .ok_or_else(|| anyhow!("Field of {:?} is out of bounds", id))?;
+            self.migrate_value(new_field.ty, field_path, src, dest)?;
+        }
+
+        for old_field in old_fields.iter() {
+            if !new_fields
+                .iter()
+                .any(|new_field| new_field.name == old_field.name)
+            {
+                self.report
+                    .dropped
+                    .push(format!("{}.{}", path, old_field.name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Field<'a> {
+    /// The field's name, or its position for unnamed fields
+    name: String,
+    ty: &'a StableId,
+    layout: FieldLayout,
+}
+
+impl Field<'_> {
+    fn bytes<'b>(&self, value: &'b [u8]) -> Option<&'b [u8]> {
+        value.get(self.layout.offset..self.layout.offset + self.layout.size)
+    }
+
+    fn bytes_mut<'b>(&self, value: &'b mut [u8]) -> Option<&'b mut [u8]> {
+        value.get_mut(self.layout.offset..self.layout.offset + self.layout.size)
+    }
+}
+
+/// Returns the fields of struct-like types, if their memory layout is known
+fn fields(signature: &TypeSignature) -> Option<Vec<Field<'_>>> {
+    let (names, types, layout): (Vec<String>, Vec<&StableId>, _) = match signature {
+        TypeSignature::Struct { fields, layout, .. } => (
+            fields.iter().map(|field| field.name.clone()).collect(),
+            fields.iter().map(|field| &field.ty).collect(),
+            layout,
+        ),
+        // Unnamed fields are matched by position
+        TypeSignature::TupleStruct { fields, layout, .. }
+        | TypeSignature::Tuple { fields, layout, .. } => (
+            (0..fields.len()).map(|index| index.to_string()).collect(),
+            fields.iter().collect(),
+            layout,
+        ),
+        _ => return None,
+    };
+
+    if layout.len() != types.len() {
+        return None;
+    }
+
+    let fields = names
+        .into_iter()
+        .zip(types)
+        .zip(layout)
+        .map(|((name, ty), layout)| Field {
+            name,
+            ty,
+            layout: *layout,
+        })
+        .collect();
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use common::FieldSignature;
+
+    use super::*;
+
+    fn primitive(name: &str) -> StableId {
+        StableId::new("unknown", name)
+    }
+
+    fn resource(fields: &[(&str, &str, usize, usize)]) -> TypeSignature {
+        TypeSignature::Struct {
+            ty: StableId::new("test_crate", "MyResource"),
+            size: Some(8),
+            align: Some(4),
+            generics: vec![],
+            fields: fields
+                .iter()
+                .map(|(name, ty, _, _)| FieldSignature {
+                    name: name.to_string(),
+                    ty: primitive(ty),
+                })
+                .collect(),
+            layout: fields
+                .iter()
+                .map(|(_, _, offset, size)| FieldLayout {
+                    offset: *offset,
+                    size: *size,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn migrate_matches_fields_by_name_and_type() {
+        let id = StableId::new("test_crate", "MyResource");
+        let old = [resource(&[
+            ("kept", "u32", 0, 4),
+            ("retyped", "u16", 4, 2),
+            ("removed", "u8", 6, 1),
+        ])];
+        let new = [resource(&[
+            ("added", "u16", 0, 2),
+            ("retyped", "i16", 2, 2),
+            ("kept", "u32", 4, 4),
+        ])];
+
+        let src = [1, 2, 3, 4, 5, 6, 7, 0];
+        let mut dest = [9, 9, 9, 9, 0, 0, 0, 0];
+
+        let mut migration = Migration::new(&old, &new);
+        migration.migrate(&id, &src, &mut dest).unwrap();
+
+        assert_eq!(dest, [9, 9, 9, 9, 1, 2, 3, 4]);
+        assert_eq!(
+            migration.report,
+            MigrationReport {
+                migrated: vec!["MyResource.kept".to_owned()],
+                defaulted: vec![
+                    "MyResource.added".to_owned(),
+                    "MyResource.retyped".to_owned()
+                ],
+                dropped: vec!["MyResource.removed".to_owned()],
+            }
+        );
+    }
+
+    #[test]
+    fn migrate_enums_that_did_not_change() {
+        let id = StableId::new("test_crate", "MyResource");
+        let state = StableId::new("test_crate", "State");
+        let enum_signature = |variants: &[&str]| TypeSignature::Enum {
+            ty: state.clone(),
+            size: Some(4),
+            align: Some(4),
+            generics: vec![],
+            variants: variants
+                .iter()
+                .map(|name| VariantSignature::Tuple {
+                    name: name.to_string(),
+                    fields: vec![primitive("u16")],
+                })
+                .collect(),
+        };
+        let resource = TypeSignature::Struct {
+            ty: id.clone(),
+            size: Some(4),
+            align: Some(4),
+            generics: vec![],
+            fields: vec![FieldSignature {
+                name: "state".to_owned(),
+                ty: state.clone(),
+            }],
+            layout: vec![FieldLayout { offset: 0, size: 4 }],
+        };
+        let src = [1, 0, 7, 0];
+
+        let old = [resource.clone(), enum_signature(&["A", "B"])];
+        let mut dest = [0; 4];
+        let mut migration = Migration::new(&old, &old);
+        migration.migrate(&id, &src, &mut dest).unwrap();
+        assert_eq!(dest, src);
+        assert_eq!(migration.report.migrated, ["MyResource.state"]);
+
+        // Variants may be numbered differently once they change
+        let new = [resource, enum_signature(&["A", "C", "B"])];
+        let mut dest = [0; 4];
+        let mut migration = Migration::new(&old, &new);
+        migration.migrate(&id, &src, &mut dest).unwrap();
+        assert_eq!(dest, [0; 4]);
+        assert_eq!(migration.report.defaulted, ["MyResource.state"]);
+    }
+
+    #[test]
+    fn migrate_resized_array() {
+        let id = primitive("[u8; 2]");
+        let array = |capacity| TypeSignature::Array {
+            ty: id.clone(),
+            generics: vec![],
+            item_ty: primitive("u8"),
+            capacity,
+        };
+        let old = [array(3)];
+        let new = [array(2)];
+
+        let mut dest = [0, 0];
+        let mut migration = Migration::new(&old, &new);
+        migration.migrate(&id, &[1, 2, 3], &mut dest).unwrap();
+
+        assert_eq!(dest, [1, 2]);
+        assert_eq!(migration.report.migrated, ["[u8; 2][0]", "[u8; 2][1]"]);
+        assert_eq!(migration.report.dropped, ["[u8; 2][2]"]);
+    }
+
+    #[test]
+    fn migrate_across_crate_versions() {
+        let id = StableId::new("test_crate", "MyResource");
+        let versioned = |version| {
+            let mut signature = resource(&[("kept", "u32", 0, 4)]);
+            *signature.stable_id_mut() = id.clone().with_version(version);
+            signature
+        };
+        let src = [1, 2, 3, 4, 0, 0, 0, 0];
+
+        // Values migrate across any version of the crate, as long as their layout allows
+        for (old, new) in [("1.2.0", "1.3.1"), ("0.3.0", "0.4.0"), ("1.0.0", "2.0.0")] {
+            let (old, new) = ([versioned(old)], [versioned(new)]);
+            let mut dest = [0; 8];
+            let mut migration = Migration::new(&old, &new);
+            migration.migrate(&id, &src, &mut dest).unwrap();
+            assert_eq!(dest, src);
+            assert_eq!(migration.report.migrated, ["MyResource.kept"]);
+        }
+    }
+}
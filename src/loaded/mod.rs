@@ -1,21 +1,139 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context as AnyhowContext, *};
+use bevy_ecs::{entity::Entity, system::Commands};
+use bevy_platform::collections::{HashMap, HashSet};
+use common::ModPackage;
+use ed25519_dalek::VerifyingKey;
 use sha2::{Digest, Sha256};
 use tracing::info;
 
 mod feature;
 pub use feature::LoadedFeature;
 
-use super::engine::{Engine, Module};
+mod host;
+pub use host::ModPanic;
+
+mod instance;
+use instance::Instance;
+
+mod migration;
+use migration::Migration;
+pub use migration::MigrationReport;
+
+mod resource;
+
+mod runner;
+pub(crate) use instance::SystemRun;
+use runner::{ModRunner, SharedRunner};
+pub(crate) use runner::{RunnerSlot, WeakRunner};
+
+use super::{
+    budget::{BudgetExceeded, ExecutionBudget, ModBudgets, ScheduleBudgetExceeded},
+    engine::{Engine, Module},
+    host_functions::HostFunctions,
+    permissions::ModPermissions,
+    schedules::ModSchedules,
+    signing::TrustStore,
+    systems::ExecutionMode,
+};
+
+/// The version of `bevy_harmonize_api` supported by the loader. Mods built against an
+/// incompatible version fail to load
+pub const API_VERSION: &str = env!("BEVY_HARMONIZE_API_VERSION");
 
 pub mod schedule;
 
 #[derive(Debug)]
 pub struct LoadedMod {
-    pub(super) manifest_hash: common::FileHash,
+    /// The package name of the mod, which identifies it across reloads
+    name: String,
+    version: semver::Version,
+    /// The path the mod was loaded from, if any
+    pub(super) source: Option<PathBuf>,
+    /// The trusted signer of the mod's package
+    signer: Option<VerifyingKey>,
+    manifest_hash: common::FileHash,
+    /// The manifest the mod was loaded with, whose required capabilities were all granted
+    manifest: common::ModManifest,
     features: Vec<LoadedFeature>,
+    /// Every system of the mod, in the order they are exported
+    systems: Vec<common::System>,
+    // Read by a debug macro
+    #[allow(dead_code)]
     module: Module,
+    runner: SharedRunner,
+}
+
+/// Returns the package name of a mod from the path of any of its files
+pub(crate) fn package_name(path: &Path) -> String {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    file_name.split('.').next().unwrap().to_owned()
+}
+
+/// Reads the ".manifest" and ".wasm" files of a mod that isn't packaged
+async fn read_loose_files(path: &Path, package_name: &str) -> Result<ModPackage> {
+    // Either files are like this: "modname/.wasm" or "modname.wasm"
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_owned()
+        .into_string()
+        .unwrap();
+
+    let directory = if file_name.is_empty() || file_name.starts_with(".") {
+        path
+    } else {
+        path.parent()
+            .ok_or(anyhow!("Failed to find file ../{:?}", path))?
+    };
+
+    let manifest_path = directory.join(format!("{}.manifest", package_name));
+    let manifest_bytes = async_fs::read(&manifest_path).await.map_err(|err| {
+        anyhow!(
+            "Failed to read manifest file {:?}: {:?}",
+            manifest_path,
+            err
+        )
+    })?;
+
+    let wasm_path = directory.join(format!("{}.wasm", package_name));
+    let wasm_bytes = async_fs::read(&wasm_path)
+        .await
+        .map_err(|err| anyhow!("Failed to read wasm file {:?}: {:?}", wasm_path, err))?;
+
+    Ok(ModPackage::new(manifest_bytes, wasm_bytes))
+}
+
+/// Whether any system of the mod is ordered around a system set published by the host
+fn uses_host_sets(features: &[LoadedFeature]) -> bool {
+    features.iter().any(|feature| {
+        feature.schedules.ids().any(|id| {
+            feature
+                .schedules
+                .get(id)
+                .is_some_and(|schedule| !schedule.host_set_constraints().is_empty())
+        })
+    })
+}
+
+/// What the host configured for loading mods, see [`crate::prelude::ModLoaderPlugin`]
+#[derive(Clone)]
+pub(crate) struct LoadSettings {
+    pub engine: Engine,
+    pub trust: TrustStore,
+    pub permissions: ModPermissions,
+    pub schedules: ModSchedules,
+    pub host_functions: HostFunctions,
+    pub budgets: ModBudgets,
+    pub execution: ExecutionMode,
+}
+
+/// An error raised by a mod system while it was running
+#[derive(Debug)]
+pub struct SystemTrap {
+    pub system: String,
+    pub error: Error,
 }
 
 impl PartialEq for LoadedMod {
@@ -26,77 +144,772 @@ impl PartialEq for LoadedMod {
 
 impl LoadedMod {
     /// Load a mod from a path. The path can be either:
+    /// - a ".hmod" package
     /// - a directory containing ".wasm" and ".manifest" files
     /// - any mod file as long as it has siblings with matching names
-    pub async fn try_from_path(engine: Engine, path: impl AsRef<Path>) -> Result<LoadedMod> {
+    pub(crate) async fn try_from_path(
+        settings: LoadSettings,
+        path: impl AsRef<Path>,
+    ) -> Result<LoadedMod> {
         let path = path.as_ref();
         info!("Loading mod from path: {:?}", path);
 
-        // Either files are like this: "modname/.wasm" or "modname.wasm"
-        let file_name = path
-            .file_name()
-            .unwrap_or_default()
-            .to_owned()
-            .into_string()
-            .unwrap();
+        let package_name = package_name(path);
 
-        let directory = if file_name.is_empty() || file_name.starts_with(".") {
-            path
+        let is_package = path
+            .extension()
+            .is_some_and(|ext| ext == ModPackage::EXTENSION);
+        let package = if is_package {
+            let bytes = async_fs::read(path)
+                .await
+                .map_err(|err| anyhow!("Failed to read package file {:?}: {:?}", path, err))?;
+            ModPackage::decode(&bytes)
+                .with_context(|| format!("Failed to read package file {:?}", path))?
         } else {
-            path.parent()
-                .ok_or(anyhow!("Failed to find file ../{:?}", path))?
+            read_loose_files(path, &package_name).await?
         };
 
-        let package_name = file_name.split('.').next().unwrap().to_owned();
+        let mut loaded = Self::try_from_package(&settings, package_name, package)
+            .await
+            .with_context(|| format!("Failed to load mod from path: {:?}", path))?;
+        loaded.source = Some(path.to_owned());
 
-        let manifest_path = directory.join(format!("{}.manifest", package_name));
-        let manifest_bytes = async_fs::read(&manifest_path).await.map_err(|err| {
-            anyhow!(
-                "Failed to read manifest file {:?}: {:?}",
-                manifest_path,
-                err
-            )
-        })?;
+        Ok(loaded)
+    }
 
-        let wasm_path = directory.join(format!("{}.wasm", package_name));
-        let wasm_bytes = async_fs::read(&wasm_path)
-            .await
-            .map_err(|err| anyhow!("Failed to read wasm file {:?}: {:?}", wasm_path, err))?;
+    /// Load a mod from a package, once its signature is checked against the trust store
+    pub(crate) async fn try_from_package(
+        settings: &LoadSettings,
+        name: String,
+        package: ModPackage,
+    ) -> Result<LoadedMod> {
+        let signer = settings.trust.verify(&name, &package)?;
 
-        Self::try_from_bytes(engine, manifest_bytes, wasm_bytes)
-            .await
-            .with_context(|| format!("Failed to load mod from path: {:?}", path))
+        let mut loaded =
+            Self::try_from_bytes(settings, name, package.manifest, package.wasm).await?;
+        loaded.signer = signer;
+
+        Ok(loaded)
     }
 
     async fn try_from_bytes(
-        engine: Engine,
+        settings: &LoadSettings,
+        name: String,
         manifest_bytes: impl AsRef<[u8]>,
         wasm_bytes: impl AsRef<[u8]>,
     ) -> Result<LoadedMod> {
-        let (manifest, _) = bincode::decode_from_slice::<common::ModManifest, _>(
-            manifest_bytes.as_ref(),
-            bincode::config::standard(),
-        )
-        .map_err(|_| anyhow!("Failed to parse manifest"))?;
+        let LoadSettings {
+            engine,
+            permissions,
+            schedules,
+            host_functions,
+            budgets,
+            execution,
+            ..
+        } = settings;
+
+        let manifest = common::ModManifest::decode(manifest_bytes.as_ref())
+            .map_err(|err| anyhow!("Failed to parse manifest: {}", err))?;
 
         let wasm_hash = common::FileHash::from_sha256(Sha256::digest(&wasm_bytes).into());
         if wasm_hash != manifest.wasm_hash {
             bail!("Wasm hash does not match manifest");
         }
 
+        if !common::is_semver_compatible(&manifest.common_version, common::VERSION) {
+            bail!(
+                "Mod was built against bevy_harmonize_common {}, which is incompatible with {}",
+                manifest.common_version,
+                common::VERSION
+            );
+        }
+        if !common::is_semver_compatible(&manifest.api_version, API_VERSION) {
+            bail!(
+                "Mod was built against bevy_harmonize_api {}, which is incompatible with {}",
+                manifest.api_version,
+                API_VERSION
+            );
+        }
+        let version = semver::Version::parse(&manifest.version)
+            .map_err(|err| anyhow!("Invalid mod version {:?}: {}", manifest.version, err))?;
+
+        permissions.check(&name, &manifest.capabilities)?;
+
+        // Features are toggled by name, and the first one is the part of the mod outside of
+        // any feature
+        for (index, feature) in manifest.features.iter().enumerate().skip(1) {
+            if manifest.features[..index]
+                .iter()
+                .any(|other| other.name == feature.name)
+            {
+                bail!(
+                    "Mod declares feature {:?} twice, or has a feature named after itself",
+                    feature.name
+                );
+            }
+        }
+
         let mut features = Vec::with_capacity(manifest.features.len());
         for feature in manifest.features.iter() {
-            features.push(LoadedFeature::try_from_descriptor(feature)?);
+            features.push(LoadedFeature::try_from_descriptor(feature, schedules)?);
+        }
+        if *execution == ExecutionMode::Parallel && uses_host_sets(&features) {
+            bail!(
+                "Mod orders systems around system sets of the host, which requires mod systems to run as bevy systems"
+            );
         }
 
         let manifest_hash = common::FileHash::from_sha256(Sha256::digest(&manifest_bytes).into());
 
-        let module = Module::new(&engine, wasm_bytes.as_ref())?;
+        // The generated export crate numbers systems in the manifest's deterministic order
+        let systems: Vec<_> = manifest.systems().into_iter().cloned().collect();
+        let exports: HashMap<_, _> = systems
+            .iter()
+            .enumerate()
+            .map(|(index, system)| (system.id, index as u32))
+            .collect();
+
+        let conditions = features
+            .iter()
+            .flat_map(|feature| {
+                let schedules = &feature.schedules;
+                schedules.ids().filter_map(|id| schedules.get(id))
+            })
+            .flat_map(|schedule| schedule.ordered_systems())
+            .filter(|(_, system)| system.is_condition)
+            .filter_map(|(id, _)| exports.get(id).copied())
+            .collect();
+
+        let memories = resource::initial_memories(&manifest)?;
+
+        let budget = budgets.get(&name);
+        let module = Module::new(engine, &wasm_hash, wasm_bytes.as_ref())?;
+        let instance = Instance::new(
+            engine,
+            &module,
+            memories,
+            &manifest.capabilities,
+            host_functions,
+            budget,
+        )?;
 
         Ok(Self {
+            name,
+            version,
+            source: None,
+            signer: None,
             manifest_hash,
+            manifest,
             features,
+            systems,
             module,
+            runner: SharedRunner::new(ModRunner {
+                instance,
+                exports,
+                budget,
+                suspended: false,
+                disabled: HashSet::new(),
+                conditions,
+            }),
         })
     }
+
+    /// The package name of the mod
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> &semver::Version {
+        &self.version
+    }
+
+    /// The trusted signer of the mod, if it was signed by one
+    pub fn signer(&self) -> Option<&VerifyingKey> {
+        self.signer.as_ref()
+    }
+
+    pub fn manifest_hash(&self) -> &common::FileHash {
+        &self.manifest_hash
+    }
+
+    /// The capabilities the mod required and was granted
+    pub fn capabilities(&self) -> &[common::Capability] {
+        &self.manifest.capabilities
+    }
+
+    /// The mods that must be started before this one
+    pub fn dependencies(&self) -> &[common::Dependency] {
+        &self.manifest.dependencies
+    }
+
+    /// The manifest the mod was loaded from
+    pub fn manifest(&self) -> &common::ModManifest {
+        &self.manifest
+    }
+
+    pub fn features(&self) -> &[LoadedFeature] {
+        &self.features
+    }
+
+    /// Every system of the mod, across all features and schedules
+    pub fn systems(&self) -> &[common::System] {
+        &self.systems
+    }
+
+    /// Carries the state of a previous version of this mod over, replacing default values
+    ///
+    /// Resource values are migrated field by field, and entities spawned by the previous
+    /// version keep their handles. The previous version is left untouched if a value fails to
+    /// migrate, so it can keep running
+    pub(crate) fn migrate_from(&mut self, previous: &mut LoadedMod) -> Result<MigrationReport> {
+        let report = self.migrate_resources(previous)?;
+
+        self.runner
+            .lock()
+            .instance
+            .inherit_entities(&mut previous.runner.lock().instance);
+
+        // Features keep being enabled or disabled across versions
+        for feature in self.features.iter_mut() {
+            if let Some(previous) = previous.features.iter().find(|p| p.name == feature.name) {
+                feature.enabled = previous.enabled;
+            }
+        }
+        self.update_disabled_systems();
+
+        Ok(report)
+    }
+
+    fn migrate_resources(&self, previous: &LoadedMod) -> Result<MigrationReport> {
+        let resources = self.resources();
+        let previous_resources = previous.resources();
+
+        let mut runner = self.runner.lock();
+        let previous_runner = previous.runner.lock();
+
+        let mut migration = Migration::new(&previous.manifest.types, &self.manifest.types);
+        for id in resources.iter() {
+            // Zero-sized resources have no memory
+            let Some(dest) = runner.instance.memory_mut(id) else {
+                continue;
+            };
+
+            match previous_runner.instance.memory(id) {
+                Some(src) => migration
+                    .migrate(id, src, dest)
+                    .with_context(|| format!("Failed to migrate {:?}", id))?,
+                None => migration.report.defaulted.push(id.name.clone()),
+            }
+        }
+        for id in previous_resources.iter() {
+            if !resources.contains(id) {
+                migration.report.dropped.push(id.name.clone());
+            }
+        }
+
+        Ok(migration.report)
+    }
+
+    pub fn budget(&self) -> ExecutionBudget {
+        self.runner.lock().budget
+    }
+
+    pub(crate) fn set_budget(&mut self, budget: ExecutionBudget) {
+        self.runner.lock().budget = budget;
+    }
+
+    /// Whether the mod was suspended after going over its budget. Suspended mods don't run
+    /// any systems
+    pub fn is_suspended(&self) -> bool {
+        self.runner.lock().suspended
+    }
+
+    pub(crate) fn resume(&mut self) {
+        self.runner.lock().suspended = false;
+    }
+
+    fn feature_index(&self, name: &str) -> Result<usize> {
+        self.features
+            .iter()
+            .position(|feature| feature.name == name)
+            .ok_or_else(|| anyhow!("Mod {:?} has no feature {:?}", self.name, name))
+    }
+
+    /// Starts or stops running the systems of the feature with the given name
+    ///
+    /// Systems that are also part of an enabled feature keep running. Startup systems don't
+    /// run again when a feature is enabled
+    pub(crate) fn set_feature_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        let index = self.feature_index(name)?;
+        self.features[index].enabled = enabled;
+        self.update_disabled_systems();
+        Ok(())
+    }
+
+    fn update_disabled_systems(&mut self) {
+        let enabled: HashSet<_> = self
+            .features
+            .iter()
+            .filter(|feature| feature.enabled)
+            .flat_map(|feature| feature.system_ids())
+            .collect();
+        let disabled = self
+            .features
+            .iter()
+            .filter(|feature| !feature.enabled)
+            .flat_map(|feature| feature.system_ids())
+            .filter(|id| !enabled.contains(id))
+            .copied()
+            .collect();
+        self.runner.lock().disabled = disabled;
+    }
+
+    /// Resets the resources of the feature with the given name to their default values
+    pub(crate) fn reset_feature(&mut self, name: &str) -> Result<()> {
+        let feature = &self.features[self.feature_index(name)?];
+        let layouts = resource::Layouts::new(&self.manifest.types);
+        let mut runner = self.runner.lock();
+        for (id, bytes) in feature.resources.iter() {
+            // Zero-sized resources have no memory
+            let Some(dest) = runner.instance.memory_mut(id) else {
+                continue;
+            };
+            let value = layouts
+                .decode(id, bytes)
+                .with_context(|| format!("Failed to decode default value of {:?}", id))?;
+            dest[..value.len()].copy_from_slice(&value);
+        }
+        Ok(())
+    }
+
+    /// A reference to the runner of this version of the mod, for its bevy systems
+    pub(crate) fn weak_runner(&self) -> WeakRunner {
+        self.runner.downgrade()
+    }
+
+    /// Takes the entities spawned by the mod, so they can be despawned once it is unloaded
+    pub(crate) fn take_entities(&mut self) -> Vec<Entity> {
+        self.runner.lock().instance.take_entities()
+    }
+
+    /// Returns the id of every resource declared by the mod, in manifest order
+    pub fn resources(&self) -> Vec<common::StableId> {
+        self.manifest
+            .types
+            .iter()
+            .map(|signature| signature.stable_id())
+            .filter(|id| {
+                self.features
+                    .iter()
+                    .any(|feature| feature.resources.contains_key(id))
+            })
+            .collect()
+    }
+
+    /// The systems and run conditions of the given schedule that are part of this mod, and
+    /// how they relate to each other
+    pub(crate) fn schedule_graph(&self, id: &common::StableId) -> ScheduleGraph {
+        let runner = self.runner.lock();
+        let mut graph = ScheduleGraph::default();
+        for feature in self.features.iter() {
+            let Some(schedule) = feature.schedules.get(id) else {
+                continue;
+            };
+
+            for (system_id, system) in schedule.ordered_systems() {
+                if !runner.exports.contains_key(system_id) {
+                    continue;
+                }
+                let metadata = common::System {
+                    id: *system_id,
+                    name: system.name.clone(),
+                    params: system.params.clone(),
+                };
+                if system.is_condition {
+                    graph.conditions.push(metadata);
+                    continue;
+                }
+                graph.systems.push(metadata);
+                graph.gated.extend(
+                    system
+                        .conditions
+                        .iter()
+                        .map(|condition| (*system_id, *condition)),
+                );
+            }
+        }
+
+        // Conditions are not systems of the schedule, so they are left out of its ordering
+        let is_system = |id| graph.systems.iter().any(|system| system.id == id);
+        for feature in self.features.iter() {
+            let Some(schedule) = feature.schedules.get(id) else {
+                continue;
+            };
+            let dependencies: Vec<_> = schedule
+                .dependencies()
+                .filter(|(before, after)| is_system(*before) && is_system(*after))
+                .collect();
+            graph.dependencies.extend(dependencies);
+        }
+        graph
+    }
+
+    /// The constraints between the systems of the given schedule and the system sets
+    /// published by the host
+    pub(crate) fn host_set_constraints(
+        &self,
+        id: &common::StableId,
+    ) -> Vec<schedule::HostSetConstraint> {
+        let runner = self.runner.lock();
+        self.features
+            .iter()
+            .filter_map(|feature| feature.schedules.get(id))
+            .flat_map(|schedule| schedule.host_set_constraints())
+            .filter(|constraint| runner.exports.contains_key(&constraint.system))
+            .cloned()
+            .collect()
+    }
+
+    /// Runs every system of the given schedule across enabled features, in dependency order
+    ///
+    /// Run conditions are evaluated once, before the first system they decide on. A trapping
+    /// condition counts as `false`. A trapping system does not prevent the remaining systems
+    /// from running, unless it went over budget and the mod is suspended for it. Once the
+    /// schedule used up its budget, the remaining systems are skipped and each reported with a
+    /// [`ScheduleBudgetExceeded`] trap
+    pub(crate) fn run_schedule(
+        &mut self,
+        id: &common::StableId,
+        commands: &mut Commands,
+    ) -> Vec<SystemTrap> {
+        let mut runner = self.runner.lock();
+        let mut run = ScheduleRun {
+            commands,
+            remaining: runner.budget.per_schedule,
+            conditions: HashMap::new(),
+            traps: Vec::new(),
+        };
+        for feature in self.features.iter().filter(|feature| feature.enabled) {
+            let Some(schedule) = feature.schedules.get(id) else {
+                continue;
+            };
+
+            for (system_id, system) in schedule.ordered_systems() {
+                // Systems only referenced by constraints are not part of this mod
+                let Some(index) = runner.exports.get(system_id).copied() else {
+                    continue;
+                };
+                if system.is_condition {
+                    continue;
+                }
+
+                let mut should_run = true;
+                for condition in system.conditions.iter() {
+                    if !run.condition(&mut runner, schedule, condition) {
+                        should_run = false;
+                        break;
+                    }
+                }
+                if !should_run {
+                    continue;
+                }
+
+                if !run.system(&mut runner, index, &system.name) {
+                    return run.traps;
+                }
+            }
+        }
+        run.traps
+    }
+}
+
+/// The parts of a mod schedule needed to add it to a bevy schedule
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ScheduleGraph {
+    pub systems: Vec<common::System>,
+    pub conditions: Vec<common::System>,
+    /// Pairs of systems that must run one after the other
+    pub dependencies: Vec<(common::SystemId, common::SystemId)>,
+    /// Pairs of systems and a run condition that must return `true` for them to run
+    pub gated: Vec<(common::SystemId, common::SystemId)>,
+}
+
+/// The state of a single run of a mod schedule
+struct ScheduleRun<'a, 'w, 's> {
+    commands: &'a mut Commands<'w, 's>,
+    /// The fuel left for the schedule, if it has a budget
+    remaining: Option<u64>,
+    /// The output of each run condition evaluated so far
+    conditions: HashMap<common::SystemId, bool>,
+    traps: Vec<SystemTrap>,
+}
+
+impl ScheduleRun<'_, '_, '_> {
+    /// Runs a system within what's left of the budget. Returns `false` once the mod is
+    /// suspended and can't run any more systems
+    fn system(&mut self, runner: &mut ModRunner, index: u32, name: &str) -> bool {
+        self.run(runner, index, name);
+        !runner.suspended
+    }
+
+    /// Returns the output of a run condition, running it the first time it is needed
+    fn condition(
+        &mut self,
+        runner: &mut ModRunner,
+        schedule: &schedule::LoadedSchedule,
+        id: &common::SystemId,
+    ) -> bool {
+        if let Some(output) = self.conditions.get(id) {
+            return *output;
+        }
+
+        let index = runner.exports.get(id).copied();
+        let name = schedule.system_name(id).unwrap_or_default();
+        let output = index
+            .and_then(|index| self.run(runner, index, name))
+            .is_some_and(|run| run.output);
+        self.conditions.insert(*id, output);
+        output
+    }
+
+    /// Runs a system, or reports it as skipped once the schedule used up its budget
+    fn run(&mut self, runner: &mut ModRunner, index: u32, name: &str) -> Option<SystemRun> {
+        if runner.suspended {
+            return None;
+        }
+
+        let result = match (self.remaining, runner.budget.per_schedule) {
+            (Some(0), Some(fuel)) => Err(Error::new(ScheduleBudgetExceeded { fuel })),
+            (Some(remaining), _) => {
+                let fuel = remaining.min(runner.budget.per_system);
+                runner.run_system(index, self.commands, fuel)
+            }
+            (None, _) => {
+                let fuel = runner.budget.per_system;
+                runner.run_system(index, self.commands, fuel)
+            }
+        };
+        match result {
+            Result::Ok(run) => {
+                self.remaining = self.remaining.map(|remaining| remaining - run.consumed);
+                Some(run)
+            }
+            Err(error) => {
+                if error.is::<BudgetExceeded>() {
+                    self.remaining = self.remaining.map(|_| 0);
+                }
+                self.traps.push(SystemTrap {
+                    system: name.to_owned(),
+                    error,
+                });
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use bevy_ecs::world::{CommandQueue, World};
+    use common::{FeatureDescriptor, FileHash, ModManifest, ScheduleDescriptor, StableId};
+    use wasmtime::Trap;
+
+    use super::*;
+    use crate::engine::EngineSettings;
+
+    /// A `run` function spending some fuel on every system
+    pub(crate) const BUSY_WAT: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "run") (param i32) (result i32)
+            (local $i i32)
+            (loop $busy
+                local.get $i
+                i32.const 1
+                i32.add
+                local.tee $i
+                i32.const 100
+                i32.lt_u
+                br_if $busy)
+            i32.const 0))"#;
+
+    pub(crate) fn settings() -> LoadSettings {
+        let engine = Engine::new(&EngineSettings {
+            cache: false,
+            ..Default::default()
+        })
+        .unwrap();
+        LoadSettings {
+            engine,
+            trust: TrustStore::default(),
+            permissions: ModPermissions::default(),
+            schedules: ModSchedules::default(),
+            host_functions: HostFunctions::default(),
+            budgets: ModBudgets::default(),
+            execution: ExecutionMode::default(),
+        }
+    }
+
+    /// A feature adding systems to `Update`, one for each name
+    pub(crate) fn feature(name: &str, systems: &[(common::SystemId, &str)]) -> FeatureDescriptor {
+        let systems = systems
+            .iter()
+            .map(|(id, name)| common::System {
+                id: *id,
+                name: (*name).to_owned(),
+                params: vec![],
+            })
+            .collect();
+        FeatureDescriptor {
+            name: name.to_owned(),
+            resources: vec![],
+            schedules: vec![ScheduleDescriptor {
+                id: StableId::from_typed::<common::Update>(),
+                schedule: common::Schedule {
+                    systems,
+                    constraints: vec![],
+                },
+            }],
+        }
+    }
+
+    pub(crate) fn manifest(features: Vec<FeatureDescriptor>) -> ModManifest {
+        ModManifest {
+            wasm_hash: FileHash::empty(),
+            version: "0.1.0".to_owned(),
+            api_version: API_VERSION.to_owned(),
+            common_version: common::VERSION.to_owned(),
+            types: vec![],
+            features,
+            capabilities: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    /// Loads a mod with wasm written as text
+    pub(crate) fn load(
+        settings: &LoadSettings,
+        name: &str,
+        mut manifest: ModManifest,
+        wat: &str,
+    ) -> Result<LoadedMod> {
+        manifest.wasm_hash = FileHash::from_sha256(Sha256::digest(wat).into());
+        let manifest = manifest.encode()?;
+        futures_lite::future::block_on(LoadedMod::try_from_bytes(
+            settings,
+            name.to_owned(),
+            manifest,
+            wat,
+        ))
+    }
+
+    #[test]
+    fn start_functions_run_within_the_budget() {
+        let wat = r#"(module
+            (memory (export "memory") 1)
+            (func $spin (loop $spin br $spin))
+            (start $spin)
+            (func (export "run") (param i32) (result i32)
+                i32.const 0))"#;
+        let mut settings = settings();
+        settings.budgets = ModBudgets::default().with_budget(
+            "spinning",
+            ExecutionBudget {
+                per_system: 1000,
+                ..Default::default()
+            },
+        );
+
+        let err = load(&settings, "spinning", manifest(vec![]), wat).unwrap_err();
+        assert_eq!(err.downcast_ref::<Trap>(), Some(&Trap::OutOfFuel));
+    }
+
+    #[test]
+    fn systems_skipped_by_the_schedule_budget_are_reported() {
+        let mut settings = settings();
+        settings.budgets = ModBudgets::new(ExecutionBudget {
+            per_system: 10_000,
+            per_schedule: Some(50),
+            suspend_on_exhaustion: false,
+        });
+        let systems = [
+            (common::SystemId::of::<[u8; 0]>(), "first"),
+            (common::SystemId::of::<[u8; 1]>(), "second"),
+            (common::SystemId::of::<[u8; 2]>(), "third"),
+        ];
+        let mut loaded = load(
+            &settings,
+            "busy",
+            manifest(vec![feature("busy", &systems)]),
+            BUSY_WAT,
+        )
+        .unwrap();
+
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let traps = loaded.run_schedule(&StableId::from_typed::<common::Update>(), &mut commands);
+
+        assert_eq!(traps.len(), 3);
+        assert!(traps[0].error.is::<BudgetExceeded>());
+        assert!(traps[1..]
+            .iter()
+            .all(|trap| trap.error.is::<ScheduleBudgetExceeded>()));
+    }
+
+    #[test]
+    fn features_are_not_named_after_the_mod() {
+        let settings = settings();
+        let features = vec![feature("particles", &[]), feature("particles", &[])];
+        let err = load(&settings, "particles", manifest(features), BUSY_WAT).unwrap_err();
+        assert!(err.to_string().contains("named after itself"));
+
+        let features = vec![feature("particles", &[]), feature("sounds", &[])];
+        assert!(load(&settings, "particles", manifest(features), BUSY_WAT).is_ok());
+    }
+
+    #[test]
+    fn mods_require_compatible_versions() {
+        let settings = settings();
+        let mut incompatible = manifest(vec![feature("particles", &[])]);
+        incompatible.common_version = "999.0.0".to_owned();
+        let err = load(&settings, "particles", incompatible, BUSY_WAT).unwrap_err();
+        assert!(err.to_string().contains("bevy_harmonize_common 999.0.0"));
+
+        let mut incompatible = manifest(vec![feature("particles", &[])]);
+        incompatible.api_version = "999.0.0".to_owned();
+        let err = load(&settings, "particles", incompatible, BUSY_WAT).unwrap_err();
+        assert!(err.to_string().contains("bevy_harmonize_api 999.0.0"));
+
+        let compatible = manifest(vec![feature("particles", &[])]);
+        assert!(load(&settings, "particles", compatible, BUSY_WAT).is_ok());
+    }
+
+    #[derive(bevy_ecs::schedule::SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+    struct Physics;
+
+    #[test]
+    fn host_sets_require_bevy_systems() {
+        let physics = StableId::new("host", "Physics");
+        let mut settings = settings();
+        settings.schedules = ModSchedules::default().with_set(physics.clone(), Physics);
+
+        let physics_mod = || {
+            let system = common::SystemId::of::<[u8; 0]>();
+            let mut feature = feature("physics", &[(system, "after_physics")]);
+            feature.schedules[0]
+                .schedule
+                .constraints
+                .push(common::Constraint::Order {
+                    before: common::SystemSet::Named(physics.clone()),
+                    after: common::SystemSet::Anonymous(vec![system]),
+                });
+            manifest(vec![feature])
+        };
+
+        let err = load(&settings, "physics", physics_mod(), BUSY_WAT).unwrap_err();
+        assert!(err.to_string().contains("system sets of the host"));
+
+        settings.execution = ExecutionMode::BevySystems;
+        assert!(load(&settings, "physics", physics_mod(), BUSY_WAT).is_ok());
+    }
 }
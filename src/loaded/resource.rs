@@ -0,0 +1,409 @@
+use std::ops::Range;
+
+use anyhow::*;
+use bevy_platform::collections::HashMap;
+use bincode::Decode;
+use common::{FieldLayout, ModManifest, StableId, TypeSignature};
+
+/// Name of the import module for resource memories, see `postprocess::transform_wasm`
+pub const MEMORY_IMPORT_MODULE: &str = "bevy";
+
+/// Returns the name a type's memory is imported under
+pub fn import_name(id: &StableId) -> String {
+    format!("{}::{}", id.crate_name, id.name)
+}
+
+/// The initial contents of a memory imported by a mod
+#[derive(Debug)]
+pub(crate) struct InitialMemory {
+    pub id: StableId,
+    /// Where the mod accesses the value, see [`common::type_addresses`]
+    pub address: Range<usize>,
+    pub bytes: Vec<u8>,
+}
+
+/// Computes the initial contents of every memory a mod imports, keyed by import name
+///
+/// Each addressable type gets its own memory with the value stored at the address the mod
+/// was built to access it at. Resources are seeded with their default value, every other
+/// type is zeroed.
+pub(crate) fn initial_memories(manifest: &ModManifest) -> Result<HashMap<String, InitialMemory>> {
+    let layouts = Layouts::new(&manifest.types);
+
+    let mut defaults = HashMap::new();
+    for feature in manifest.features.iter() {
+        for (id, bytes) in feature.resources.iter() {
+            defaults.entry(id).or_insert(bytes);
+        }
+    }
+
+    let mut memories = HashMap::new();
+    // Addresses are bounded by `common::MAX_ADDRESSABLE_SIZE`, and so are the values below
+    for (signature, address) in common::type_addresses(&manifest.types)? {
+        let address = address.start as usize..address.end as usize;
+        let id = signature.stable_id();
+        let bytes = match defaults.get(&id) {
+            Some(default) => layouts
+                .decode(&id, default)
+                .with_context(|| format!("Failed to decode default value of {:?}", id))?,
+            None => vec![0; address.len()],
+        };
+
+        memories.insert(import_name(&id), InitialMemory { id, address, bytes });
+    }
+
+    Ok(memories)
+}
+
+/// Lays out values in memory following the type signatures of a manifest
+pub(crate) struct Layouts<'a>(HashMap<StableId, &'a TypeSignature>);
+
+impl<'a> Layouts<'a> {
+    pub fn new(types: &'a [TypeSignature]) -> Self {
+        Self(types.iter().map(|ty| (ty.stable_id(), ty)).collect())
+    }
+
+    pub fn get(&self, id: &StableId) -> Option<&'a TypeSignature> {
+        self.0.get(id).copied()
+    }
+
+    /// The size of a type in memory, if known
+    fn size(&self, id: &StableId) -> Option<usize> {
+        match Primitive::from_id(id) {
+            Some(primitive) => Some(primitive.size()),
+            None => self.get(id)?.size(),
+        }
+    }
+
+    /// Decodes a value serialized with bevy_reflect and bincode into its in-memory representation
+    pub fn decode(&self, id: &StableId, bytes: &[u8]) -> Result<Vec<u8>> {
+        let size = self
+            .size(id)
+            .ok_or_else(|| anyhow!("Size of {:?} is unknown", id))?;
+        if size > common::MAX_ADDRESSABLE_SIZE as usize {
+            bail!("{:?} is too large to be stored in memory", id);
+        }
+
+        let mut reader = bytes;
+        let mut dest = vec![0; size];
+        self.decode_into(id, &mut reader, &mut dest)?;
+
+        if !reader.is_empty() {
+            bail!("{} trailing bytes after value", reader.len());
+        }
+
+        Ok(dest)
+    }
+
+    fn decode_into(&self, id: &StableId, reader: &mut &[u8], dest: &mut [u8]) -> Result<()> {
+        if let Some(primitive) = Primitive::from_id(id) {
+            if primitive.size() != dest.len() {
+                bail!("Expected {:?} to be {} bytes", id, dest.len());
+            }
+            return primitive.decode_into(reader, dest);
+        }
+
+        let signature = self
+            .0
+            .get(id)
+            .ok_or_else(|| anyhow!("Missing type signature for {:?}", id))?;
+
+        match signature {
+            TypeSignature::Struct { fields, layout, .. } => {
+                let fields = fields.iter().map(|field| &field.ty);
+                self.decode_fields(id, fields, layout, reader, dest)
+            }
+            TypeSignature::TupleStruct { fields, layout, .. }
+            | TypeSignature::Tuple { fields, layout, .. } => {
+                self.decode_fields(id, fields.iter(), layout, reader, dest)
+            }
+            TypeSignature::Array {
+                item_ty, capacity, ..
+            } => {
+                if *capacity == 0 {
+                    return Ok(());
+                }
+
+                // Array items are tightly packed, so they evenly divide the array
+                let stride = self
+                    .size(item_ty)
+                    .ok_or_else(|| anyhow!("Size of {:?} is unknown", item_ty))?;
+                if stride == 0 || stride.checked_mul(*capacity) != Some(dest.len()) {
+                    bail!(
+                        "Expected {} items of {:?} to take {} bytes",
+                        capacity,
+                        item_ty,
+                        dest.len()
+                    );
+                }
+                for item in dest.chunks_exact_mut(stride) {
+                    self.decode_into(item_ty, reader, item)?;
+                }
+                Ok(())
+            }
+            TypeSignature::List { .. }
+            | TypeSignature::Map { .. }
+            | TypeSignature::Set { .. }
+            | TypeSignature::Enum { .. }
+            | TypeSignature::Opaque { .. } => {
+                bail!("{:?} cannot be stored in resource memory", id)
+            }
+        }
+    }
+
+    fn decode_fields<'b>(
+        &self,
+        id: &StableId,
+        fields: impl ExactSizeIterator<Item = &'b StableId>,
+        layout: &[FieldLayout],
+        reader: &mut &[u8],
+        dest: &mut [u8],
+    ) -> Result<()> {
+        if fields.len() != layout.len() {
+            bail!("Memory layout of {:?} is unknown", id);
+        }
+
+        // Fields are serialized in declaration order, regardless of where they are in memory
+        for (field, FieldLayout { offset, size }) in fields.zip(layout) {
+            let field_dest = dest
+                .get_mut(*offset..*offset + *size)
+                .ok_or_else(|| anyhow!("Field of {:?} is out of bounds", id))?;
+            self.decode_into(field, reader, field_dest)?;
+        }
+        Ok(())
+    }
+}
+
+/// Primitive types, as laid out in 32-bit wasm memory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Primitive {
+    Bool,
+    Char,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    Usize,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Isize,
+    F32,
+    F64,
+}
+
+impl Primitive {
+    pub fn from_id(id: &StableId) -> Option<Self> {
+        if id.crate_name != "unknown" {
+            return None;
+        }
+
+        let primitive = match id.name.as_str() {
+            "bool" => Self::Bool,
+            "char" => Self::Char,
+            "u8" => Self::U8,
+            "u16" => Self::U16,
+            "u32" => Self::U32,
+            "u64" => Self::U64,
+            "u128" => Self::U128,
+            "usize" => Self::Usize,
+            "i8" => Self::I8,
+            "i16" => Self::I16,
+            "i32" => Self::I32,
+            "i64" => Self::I64,
+            "i128" => Self::I128,
+            "isize" => Self::Isize,
+            "f32" => Self::F32,
+            "f64" => Self::F64,
+            _ => return None,
+        };
+        Some(primitive)
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::Bool | Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::Char | Self::U32 | Self::I32 | Self::F32 | Self::Usize | Self::Isize => 4,
+            Self::U64 | Self::I64 | Self::F64 => 8,
+            Self::U128 | Self::I128 => 16,
+        }
+    }
+
+    fn decode_into(self, reader: &mut &[u8], dest: &mut [u8]) -> Result<()> {
+        match self {
+            Self::Bool => dest.copy_from_slice(&[read::<bool>(reader)? as u8]),
+            Self::Char => dest.copy_from_slice(&(read::<char>(reader)? as u32).to_le_bytes()),
+            Self::U8 => dest.copy_from_slice(&read::<u8>(reader)?.to_le_bytes()),
+            Self::U16 => dest.copy_from_slice(&read::<u16>(reader)?.to_le_bytes()),
+            Self::U32 => dest.copy_from_slice(&read::<u32>(reader)?.to_le_bytes()),
+            Self::U64 => dest.copy_from_slice(&read::<u64>(reader)?.to_le_bytes()),
+            Self::U128 => dest.copy_from_slice(&read::<u128>(reader)?.to_le_bytes()),
+            Self::Usize => {
+                let value = u32::try_from(read::<u64>(reader)?)?;
+                dest.copy_from_slice(&value.to_le_bytes())
+            }
+            Self::I8 => dest.copy_from_slice(&read::<i8>(reader)?.to_le_bytes()),
+            Self::I16 => dest.copy_from_slice(&read::<i16>(reader)?.to_le_bytes()),
+            Self::I32 => dest.copy_from_slice(&read::<i32>(reader)?.to_le_bytes()),
+            Self::I64 => dest.copy_from_slice(&read::<i64>(reader)?.to_le_bytes()),
+            Self::I128 => dest.copy_from_slice(&read::<i128>(reader)?.to_le_bytes()),
+            Self::Isize => {
+                let value = i32::try_from(read::<i64>(reader)?)?;
+                dest.copy_from_slice(&value.to_le_bytes())
+            }
+            Self::F32 => dest.copy_from_slice(&read::<f32>(reader)?.to_le_bytes()),
+            Self::F64 => dest.copy_from_slice(&read::<f64>(reader)?.to_le_bytes()),
+        }
+        Ok(())
+    }
+}
+
+/// Reads a single bincode value, advancing the reader past it
+fn read<T: Decode<()>>(reader: &mut &[u8]) -> Result<T> {
+    let (value, read) = bincode::decode_from_slice(reader, bincode::config::standard())?;
+    *reader = &reader[read..];
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use common::FieldSignature;
+
+    use super::*;
+
+    fn primitive(name: &str) -> StableId {
+        StableId::new("unknown", name)
+    }
+
+    #[test]
+    fn decode_struct_with_reordered_fields() {
+        let id = StableId::new("test_crate", "MyResource");
+        let tuple = primitive("(u16, bool)");
+        let array = primitive("[u8; 3]");
+        let types = vec![
+            TypeSignature::Struct {
+                ty: id.clone(),
+                size: Some(16),
+                align: Some(4),
+                generics: vec![],
+                fields: vec![
+                    FieldSignature {
+                        name: "x".to_owned(),
+                        ty: primitive("u8"),
+                    },
+                    FieldSignature {
+                        name: "y".to_owned(),
+                        ty: primitive("u32"),
+                    },
+                    FieldSignature {
+                        name: "z".to_owned(),
+                        ty: tuple.clone(),
+                    },
+                    FieldSignature {
+                        name: "w".to_owned(),
+                        ty: array.clone(),
+                    },
+                ],
+                // Rust is free to reorder fields in memory
+                layout: vec![
+                    FieldLayout { offset: 8, size: 1 },
+                    FieldLayout { offset: 0, size: 4 },
+                    FieldLayout { offset: 4, size: 4 },
+                    FieldLayout { offset: 9, size: 3 },
+                ],
+            },
+            TypeSignature::Tuple {
+                ty: tuple,
+                size: None,
+                align: None,
+                generics: vec![],
+                fields: vec![primitive("u16"), primitive("bool")],
+                layout: vec![
+                    FieldLayout { offset: 0, size: 2 },
+                    FieldLayout { offset: 2, size: 1 },
+                ],
+            },
+            TypeSignature::Array {
+                ty: array,
+                generics: vec![],
+                item_ty: primitive("u8"),
+                capacity: 3,
+            },
+        ];
+
+        // MyResource { x: 1, y: 300, z: (5, true), w: [7, 8, 9] }
+        let bytes = [1, 251, 44, 1, 5, 1, 7, 8, 9];
+
+        let decoded = Layouts::new(&types).decode(&id, &bytes).unwrap();
+        assert_eq!(decoded, [44, 1, 0, 0, 5, 0, 1, 0, 1, 7, 8, 9, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decode_requires_known_layout() {
+        let id = StableId::new("test_crate", "MyResource");
+        let types = vec![TypeSignature::TupleStruct {
+            ty: id.clone(),
+            size: Some(4),
+            align: Some(4),
+            generics: vec![],
+            fields: vec![primitive("u32")],
+            layout: vec![],
+        }];
+
+        assert!(Layouts::new(&types).decode(&id, &[1]).is_err());
+    }
+
+    #[test]
+    fn decode_checks_array_layout() {
+        let id = StableId::new("test_crate", "MyResource");
+        let array = primitive("[u32; 3]");
+        let types = |capacity| {
+            vec![
+                TypeSignature::TupleStruct {
+                    ty: id.clone(),
+                    size: Some(4),
+                    align: Some(4),
+                    generics: vec![],
+                    fields: vec![array.clone()],
+                    layout: vec![FieldLayout { offset: 0, size: 4 }],
+                },
+                TypeSignature::Array {
+                    ty: array.clone(),
+                    generics: vec![],
+                    item_ty: primitive("u32"),
+                    capacity,
+                },
+            ]
+        };
+
+        // More items than the memory of the array holds
+        for capacity in [3, usize::MAX] {
+            let types = types(capacity);
+            assert!(Layouts::new(&types).decode(&id, &[1, 2, 3]).is_err());
+        }
+        let types = types(1);
+        assert_eq!(
+            Layouts::new(&types).decode(&id, &[7]).unwrap(),
+            [7, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn decode_rejects_trailing_bytes() {
+        let id = primitive("u32");
+        let types = vec![TypeSignature::Opaque {
+            ty: id.clone(),
+            size: Some(4),
+            align: Some(4),
+            generics: vec![],
+        }];
+
+        let layouts = Layouts::new(&types);
+        assert_eq!(layouts.decode(&id, &[7]).unwrap(), [7, 0, 0, 0]);
+        assert!(layouts.decode(&id, &[7, 7]).is_err());
+    }
+}
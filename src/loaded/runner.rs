@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::*;
+use bevy_ecs::system::Commands;
+use bevy_platform::collections::{HashMap, HashSet};
+
+use super::instance::{Instance, SystemRun};
+use crate::budget::{BudgetExceeded, ExecutionBudget};
+
+/// Everything needed to run the systems of a loaded mod
+///
+/// Shared with the bevy systems of the mod, see [`crate::prelude::ExecutionMode`]
+#[derive(Debug)]
+pub(crate) struct ModRunner {
+    pub instance: Instance,
+    /// Maps each system to the index it is exported under by the mod's `run` function
+    pub exports: HashMap<common::SystemId, u32>,
+    pub budget: ExecutionBudget,
+    /// Whether the mod stopped running after going over its budget
+    pub suspended: bool,
+    /// Systems that don't run because every feature they are part of is disabled
+    pub disabled: HashSet<common::SystemId>,
+    /// Export indices of run conditions, which may not change resources or spawn entities
+    pub conditions: HashSet<u32>,
+}
+
+impl ModRunner {
+    /// Runs the system exported under the given index with at most the given fuel
+    ///
+    /// Going over budget suspends the mod, if its budget says so
+    pub fn run_system(
+        &mut self,
+        index: u32,
+        commands: &mut Commands,
+        fuel: u64,
+    ) -> Result<SystemRun> {
+        let read_only = self.conditions.contains(&index);
+        let result = self.instance.run_system(index, read_only, commands, fuel);
+        if result.as_ref().is_err_and(|err| err.is::<BudgetExceeded>()) {
+            self.suspended = self.budget.suspend_on_exhaustion;
+        }
+        result
+    }
+}
+
+/// A [`ModRunner`] shared between a mod and its bevy systems
+#[derive(Debug, Clone)]
+pub(crate) struct SharedRunner(Arc<Mutex<ModRunner>>);
+
+impl SharedRunner {
+    pub fn new(runner: ModRunner) -> Self {
+        Self(Arc::new(Mutex::new(runner)))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, ModRunner> {
+        // Mods trap instead of panicking, so the lock is never poisoned
+        self.0.lock().unwrap()
+    }
+
+    /// A reference that stops working once the mod is unloaded or replaced
+    pub fn downgrade(&self) -> WeakRunner {
+        WeakRunner(Arc::downgrade(&self.0))
+    }
+}
+
+/// Never upgrades when created with [`Default`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WeakRunner(std::sync::Weak<Mutex<ModRunner>>);
+
+impl WeakRunner {
+    pub fn upgrade(&self) -> Option<SharedRunner> {
+        self.0.upgrade().map(SharedRunner)
+    }
+}
+
+/// A [`WeakRunner`] that can be swapped for the runner of a later version of the mod
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RunnerSlot(Arc<Mutex<WeakRunner>>);
+
+impl RunnerSlot {
+    pub fn new(runner: WeakRunner) -> Self {
+        Self(Arc::new(Mutex::new(runner)))
+    }
+
+    pub fn set(&self, runner: WeakRunner) {
+        *self.0.lock().unwrap() = runner;
+    }
+
+    pub fn upgrade(&self) -> Option<SharedRunner> {
+        self.0.lock().unwrap().upgrade()
+    }
+}
@@ -2,8 +2,13 @@ use std::hash::{Hash, Hasher};
 
 use anyhow::*;
 use bevy_platform::collections::{HashMap, HashSet};
-use common::{StableId, Start, Update};
-use petgraph::{algo::TarjanScc, prelude::*};
+use common::StableId;
+use petgraph::{
+    algo::{toposort, TarjanScc},
+    prelude::*,
+};
+
+use crate::schedules::ModSchedules;
 
 type Dag<T> = DiGraphMap<T, ()>;
 
@@ -15,37 +20,48 @@ pub struct LoadedSchedules(HashMap<StableId, LoadedSchedule>);
 impl LoadedSchedules {
     pub fn try_from_schedule_descriptors(
         descriptors: &Vec<common::ScheduleDescriptor>,
+        known: &ModSchedules,
     ) -> Result<Self> {
         let mut schedules: HashMap<StableId, Vec<&common::Schedule>> = HashMap::default();
 
-        // Allow only the default schedules for now
-        schedules.insert(StableId::from_typed::<Start>(), Vec::new());
-        schedules.insert(StableId::from_typed::<Update>(), Vec::new());
-
         // Group together schedules with the same schedule id
         for descriptor in descriptors {
             let schedule_id = descriptor.id.to_owned();
+            if !known.contains(&schedule_id) {
+                bail!(
+                    "Schedule {}::{} is not registered by the host",
+                    schedule_id.crate_name,
+                    schedule_id.name
+                );
+            }
             schedules
-                .get_mut(&schedule_id)
-                .ok_or(anyhow!(
-                    "Schedule descriptor with id {:?} not found",
-                    schedule_id
-                ))?
+                .entry(schedule_id)
+                .or_default()
                 .push(&descriptor.schedule);
         }
 
         let mut inner = HashMap::default();
         for (id, schedules) in schedules {
             if !schedules.is_empty() {
-                let loaded = LoadedSchedule::try_from_schedules(&schedules[..]).map_err(|err| {
-                    anyhow!("Failed to load schedule with id {:?}: {:?}", id, err)
-                })?;
+                let loaded =
+                    LoadedSchedule::try_from_schedules(&schedules[..], known).map_err(|err| {
+                        anyhow!("Failed to load schedule with id {:?}: {:?}", id, err)
+                    })?;
                 inner.insert(id, loaded);
             }
         }
 
         Ok(Self(inner))
     }
+
+    pub fn get(&self, id: &StableId) -> Option<&LoadedSchedule> {
+        self.0.get(id)
+    }
+
+    /// The ids of the schedules with at least one system
+    pub fn ids(&self) -> impl Iterator<Item = &StableId> {
+        self.0.keys()
+    }
 }
 
 // These fields are read by a debug macro
@@ -54,21 +70,47 @@ impl LoadedSchedules {
 pub struct LoadedSchedule {
     systems: HashMap<common::SystemId, LoadedSystem>,
     dependency: Dag<common::SystemId>,
+    /// A run order for all systems that respects the dependency graph
+    order: Vec<common::SystemId>,
+    host_sets: Vec<HostSetConstraint>,
+}
+
+/// How a system must run relative to a system set published by the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostSetRelation {
+    Before,
+    After,
+    Within,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostSetConstraint {
+    pub system: common::SystemId,
+    pub relation: HostSetRelation,
+    pub set: StableId,
 }
 
 // These fields are read by a debug macro
 #[allow(dead_code)]
 #[derive(Debug)]
-struct LoadedSystem {
+pub struct LoadedSystem {
     /// Whether or not this system depends on any other system
     /// In the case this is false, the scheduler can run this system first
-    is_dependent: bool,
-    name: String,
-    params: Vec<common::Param>,
+    pub is_dependent: bool,
+    pub name: String,
+    pub params: Vec<common::Param>,
+    /// The run conditions that must all return `true` for this system to run
+    pub conditions: Vec<common::SystemId>,
+    /// Whether this system is a run condition, which is only run to decide whether other
+    /// systems run
+    pub is_condition: bool,
 }
 
 impl LoadedSchedule {
-    pub fn try_from_schedules(schedules: &[&common::Schedule]) -> Result<Self> {
+    pub fn try_from_schedules(
+        schedules: &[&common::Schedule],
+        known: &ModSchedules,
+    ) -> Result<Self> {
         let mut builder = Builder::default();
 
         // Add constraints to the dependency graph
@@ -78,7 +120,7 @@ impl LoadedSchedule {
             }
         }
 
-        let mut loaded_schedules = builder.build()?;
+        let mut loaded_schedules = builder.build(known)?;
 
         // Add missing parameters to the systems
         for schedule in schedules {
@@ -87,20 +129,68 @@ impl LoadedSchedule {
                     is_dependent: false,
                     name: String::new(),
                     params: Vec::new(),
+                    conditions: Vec::new(),
+                    is_condition: false,
                 });
                 system.name = name.clone();
                 system.params = params.iter().map(common::Param::to_owned).collect();
+
+                // Systems without any constraints are absent from the dependency graph
+                if !loaded_schedules.order.contains(id) {
+                    loaded_schedules.order.push(*id);
+                }
+            }
+        }
+
+        for system in loaded_schedules.systems.values() {
+            let writes = system.params.iter().any(|param| {
+                matches!(
+                    param,
+                    common::Param::Command | common::Param::Res { mutable: true, .. }
+                )
+            });
+            if system.is_condition && writes {
+                bail!(
+                    "Run condition {} can't change resources or use commands",
+                    system.name
+                );
             }
         }
 
         Ok(loaded_schedules)
     }
+
+    /// Iterates over the edges of the dependency graph, from each system to one that must run
+    /// after it
+    pub fn dependencies(&self) -> impl Iterator<Item = (common::SystemId, common::SystemId)> + '_ {
+        self.dependency
+            .all_edges()
+            .map(|(before, after, _)| (before, after))
+    }
+
+    pub fn system_name(&self, id: &common::SystemId) -> Option<&str> {
+        self.systems.get(id).map(|system| system.name.as_str())
+    }
+
+    /// The constraints between systems and the system sets published by the host
+    pub fn host_set_constraints(&self) -> &[HostSetConstraint] {
+        &self.host_sets
+    }
+
+    /// Iterates over systems in an order that respects the dependency graph
+    pub fn ordered_systems(&self) -> impl Iterator<Item = (&common::SystemId, &LoadedSystem)> {
+        self.order
+            .iter()
+            .filter_map(|id| self.systems.get_key_value(id))
+    }
 }
 
 #[derive(Default)]
 struct Builder {
     dependency: Dag<Node>,
     sets: HashMap<SystemSet, usize>,
+    /// Run conditions, with the first node of the set they decide on
+    conditions: Vec<(common::SystemId, Node)>,
 }
 
 impl Builder {
@@ -112,11 +202,12 @@ impl Builder {
                 self.dependency.add_edge(before, after, ());
             }
             common::Constraint::Condition { set, condition } => {
-                let condition = Node::System(*condition);
                 let (after, _) = self.populate_set_nodes(set)?;
+                self.conditions.push((*condition, after));
 
                 // The condition must run before the first node of the set
-                self.dependency.add_edge(condition, after, ());
+                self.dependency
+                    .add_edge(Node::System(*condition), after, ());
             }
             common::Constraint::Includes { parent_name, set } => {
                 let parent = SystemSet::Named(parent_name.to_owned());
@@ -178,7 +269,9 @@ impl Builder {
         (Node::SetStart(id), Node::SetEnd(id))
     }
 
-    fn build(self) -> Result<LoadedSchedule> {
+    fn build(self, known: &ModSchedules) -> Result<LoadedSchedule> {
+        let host_sets = self.host_set_constraints(known)?;
+
         let mut cycles: Vec<Vec<_>> = Vec::new();
         let mut reverse_nodes = Vec::with_capacity(self.dependency.node_count());
         TarjanScc::new().run(&self.dependency, |scc| {
@@ -222,17 +315,110 @@ impl Builder {
                     is_dependent,
                     name: String::new(),
                     params: Vec::new(),
+                    conditions: Vec::new(),
+                    is_condition: false,
                 },
             );
             self.add_node_dependents_to_flattened(&mut dependency, id, Node::System(id));
         }
 
+        for (condition, start) in self.conditions.iter() {
+            let gated = match start {
+                Node::System(system) => vec![*system],
+                _ => self.closest_systems(*start, Direction::Outgoing),
+            };
+            for system in gated {
+                if let Some(system) = systems.get_mut(&system) {
+                    if !system.conditions.contains(condition) {
+                        system.conditions.push(*condition);
+                    }
+                }
+            }
+            if let Some(condition) = systems.get_mut(condition) {
+                condition.is_condition = true;
+            }
+        }
+
+        let order = toposort(&dependency, None)
+            .map_err(|cycle| anyhow!("Cycle detected at system {:?}", cycle.node_id()))?;
+
         Ok(LoadedSchedule {
             systems,
             dependency,
+            order,
+            host_sets,
         })
     }
 
+    /// Finds the systems that must run before, after or within the sets published by the host
+    ///
+    /// Named sets that are neither published by the host nor contain any system are most
+    /// likely typos, or sets the host stopped publishing, so they are refused
+    fn host_set_constraints(&self, known: &ModSchedules) -> Result<Vec<HostSetConstraint>> {
+        let mut constraints = Vec::new();
+        let mut unknown = Vec::new();
+        for (set, index) in self.sets.iter() {
+            let SystemSet::Named(id) = set else {
+                continue;
+            };
+            let (start, end) = (Node::SetStart(*index), Node::SetEnd(*index));
+
+            if known.get_set(id).is_none() {
+                let is_empty = self
+                    .dependency
+                    .neighbors_directed(start, Direction::Outgoing)
+                    .next()
+                    .is_none();
+                if is_empty {
+                    unknown.push(format!("{}::{}", id.crate_name, id.name));
+                }
+                continue;
+            }
+
+            for (relation, node, direction) in [
+                (HostSetRelation::Before, start, Direction::Incoming),
+                (HostSetRelation::After, end, Direction::Outgoing),
+                (HostSetRelation::Within, start, Direction::Outgoing),
+            ] {
+                for system in self.closest_systems(node, direction) {
+                    constraints.push(HostSetConstraint {
+                        system,
+                        relation,
+                        set: id.clone(),
+                    });
+                }
+            }
+        }
+
+        if !unknown.is_empty() {
+            unknown.sort();
+            bail!(
+                "Unknown system sets, which are neither published by the host nor contain any system: {}",
+                unknown.join(", ")
+            );
+        }
+        Ok(constraints)
+    }
+
+    /// The systems reached first when walking the graph from a node in the given direction
+    fn closest_systems(&self, node: Node, direction: Direction) -> Vec<common::SystemId> {
+        let mut systems = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            for next in self.dependency.neighbors_directed(node, direction) {
+                match next {
+                    Node::System(system) => systems.push(system),
+                    _ if visited.insert(next) => stack.push(next),
+                    _ => {}
+                }
+            }
+        }
+        systems.sort();
+        systems.dedup();
+        systems
+    }
+
     fn add_node_dependents_to_flattened(
         &self,
         dependency: &mut Dag<common::SystemId>,
@@ -279,3 +465,163 @@ enum Node {
     SetStart(usize),
     SetEnd(usize),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system<T: 'static>() -> common::System {
+        common::System {
+            id: common::SystemId::of::<T>(),
+            name: std::any::type_name::<T>().to_owned(),
+            params: Vec::new(),
+        }
+    }
+
+    fn set<T: 'static>() -> common::SystemSet {
+        common::SystemSet::Anonymous(vec![common::SystemId::of::<T>()])
+    }
+
+    #[derive(bevy_ecs::schedule::SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+    struct Physics;
+
+    #[test]
+    fn systems_are_ordered_around_host_sets() {
+        let physics = StableId::new("host", "Physics");
+        let known = ModSchedules::default().with_set(physics.clone(), Physics);
+        let host = || common::SystemSet::Named(physics.clone());
+
+        let schedule = common::Schedule {
+            systems: vec![system::<u8>(), system::<u16>(), system::<u32>()],
+            constraints: vec![
+                common::Constraint::Order {
+                    before: set::<u8>(),
+                    after: host(),
+                },
+                common::Constraint::Order {
+                    before: host(),
+                    after: set::<u16>(),
+                },
+                common::Constraint::Includes {
+                    parent_name: physics.clone(),
+                    set: set::<u32>(),
+                },
+            ],
+        };
+        let loaded = LoadedSchedule::try_from_schedules(&[&schedule], &known).unwrap();
+
+        let constraint = |system, relation| HostSetConstraint {
+            system,
+            relation,
+            set: physics.clone(),
+        };
+        let constraints = loaded.host_set_constraints();
+        assert_eq!(constraints.len(), 3);
+        assert!(constraints.contains(&constraint(
+            common::SystemId::of::<u8>(),
+            HostSetRelation::Before
+        )));
+        assert!(constraints.contains(&constraint(
+            common::SystemId::of::<u16>(),
+            HostSetRelation::After
+        )));
+        assert!(constraints.contains(&constraint(
+            common::SystemId::of::<u32>(),
+            HostSetRelation::Within
+        )));
+    }
+
+    #[test]
+    fn conditions_gate_their_sets() {
+        let named = StableId::new("mod", "Set");
+        let schedule = common::Schedule {
+            systems: vec![
+                system::<u8>(),
+                system::<u16>(),
+                system::<u32>(),
+                system::<bool>(),
+            ],
+            constraints: vec![
+                common::Constraint::Includes {
+                    parent_name: named.clone(),
+                    set: common::SystemSet::Anonymous(vec![
+                        common::SystemId::of::<u8>(),
+                        common::SystemId::of::<u16>(),
+                    ]),
+                },
+                common::Constraint::Condition {
+                    set: common::SystemSet::Named(named),
+                    condition: common::SystemId::of::<bool>(),
+                },
+            ],
+        };
+        let loaded =
+            LoadedSchedule::try_from_schedules(&[&schedule], &ModSchedules::default()).unwrap();
+
+        let systems: Vec<_> = loaded.ordered_systems().collect();
+        let condition = common::SystemId::of::<bool>();
+        for (id, system) in systems.iter() {
+            match **id {
+                id if id == condition => assert!(system.is_condition),
+                id if id == common::SystemId::of::<u32>() => {
+                    assert!(system.conditions.is_empty())
+                }
+                _ => assert_eq!(system.conditions, vec![condition]),
+            }
+        }
+
+        // Conditions run before the systems they decide on
+        let position = |id| systems.iter().position(|(other, _)| **other == id).unwrap();
+        assert!(position(condition) < position(common::SystemId::of::<u8>()));
+        assert!(position(condition) < position(common::SystemId::of::<u16>()));
+    }
+
+    #[test]
+    fn conditions_are_read_only() {
+        let mut condition = system::<bool>();
+        condition.params = vec![common::Param::Res {
+            mutable: true,
+            id: StableId::new("mod", "Counter"),
+        }];
+        let schedule = common::Schedule {
+            systems: vec![system::<u8>(), condition],
+            constraints: vec![common::Constraint::Condition {
+                set: set::<u8>(),
+                condition: common::SystemId::of::<bool>(),
+            }],
+        };
+        let err =
+            LoadedSchedule::try_from_schedules(&[&schedule], &ModSchedules::default()).unwrap_err();
+        assert!(err.to_string().contains("can't change resources"));
+    }
+
+    #[test]
+    fn unknown_sets_are_refused() {
+        let schedule = common::Schedule {
+            systems: vec![system::<u8>()],
+            constraints: vec![common::Constraint::Order {
+                before: set::<u8>(),
+                after: common::SystemSet::Named(StableId::new("host", "Physics")),
+            }],
+        };
+        let err =
+            LoadedSchedule::try_from_schedules(&[&schedule], &ModSchedules::default()).unwrap_err();
+        assert!(err.to_string().contains("host::Physics"));
+
+        // Sets of the mod itself are fine
+        let schedule = common::Schedule {
+            systems: vec![system::<u8>(), system::<u16>()],
+            constraints: vec![
+                common::Constraint::Includes {
+                    parent_name: StableId::new("mod", "Set"),
+                    set: set::<u16>(),
+                },
+                common::Constraint::Order {
+                    before: set::<u8>(),
+                    after: common::SystemSet::Named(StableId::new("mod", "Set")),
+                },
+            ],
+        };
+        assert!(LoadedSchedule::try_from_schedules(&[&schedule], &ModSchedules::default()).is_ok());
+    }
+}
@@ -1,77 +1,1117 @@
-use std::{future::Future, path::Path};
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::*;
-use bevy_app::{App, Plugin, Update};
-use bevy_ecs::system::ResMut;
+use async_channel::{Receiver, Sender};
+use bevy_app::{App, First, Last, Plugin, Update};
+use bevy_asset::{AssetApp, AssetId, AssetServer};
+use bevy_ecs::{
+    entity::Entity,
+    event::EventWriter,
+    schedule::{IntoScheduleConfigs, ScheduleLabel, Schedules},
+    system::{Commands, ParallelCommands, Res, ResMut},
+    world::{Mut, World},
+};
 use bevy_ecs_macros::Resource;
-use bevy_tasks::{block_on, poll_once, AsyncComputeTaskPool, Task};
+use bevy_platform::collections::{HashMap, HashSet};
+use bevy_tasks::{block_on, poll_once, AsyncComputeTaskPool, ComputeTaskPool, Task, TaskPool};
+use common::{Dependency, ManifestDiff, StableId};
 use tracing::{error, info, warn};
 
-use crate::{engine::Engine, loaded::LoadedMod};
+use crate::{
+    asset::{handle_mod_assets, ModAsset, ModAssetLoader},
+    budget::{ExecutionBudget, ModBudgets},
+    engine::{Engine, EngineSettings},
+    events::*,
+    host_functions::HostFunctions,
+    loaded::{package_name, LoadSettings, LoadedMod, SystemTrap},
+    permissions::ModPermissions,
+    schedules::{startup_schedules, ModSchedules},
+    signing::TrustStore,
+    systems::{add_mod_systems, AddedSystems, ExecutionMode},
+};
 
 /// A plugin that enables loading bevy_harmonize mods at runtime.
-pub struct ModLoaderPlugin;
+///
+/// Mods can be loaded as [`ModAsset`]s if the `AssetPlugin` was added before this plugin
+#[derive(Default)]
+pub struct ModLoaderPlugin {
+    /// The signers trusted to publish mods, and what to do with mods they didn't sign
+    pub trust: TrustStore,
+    /// The capabilities granted to mods. Mods requiring any capability that isn't granted
+    /// fail to load
+    pub permissions: ModPermissions,
+    /// The functions mods granted [`crate::prelude::Capability::HostFunction`] can call
+    pub host_functions: HostFunctions,
+    /// How much work mod systems may do before they are interrupted
+    pub budgets: ModBudgets,
+    /// Settings of the engine that compiles and runs mods
+    pub engine: EngineSettings,
+    /// Whether mod systems run from the mod loader, or as bevy systems of their own
+    pub execution: ExecutionMode,
+    /// The schedules mods may add systems to, and the bevy schedules that run them
+    pub schedules: ModSchedules,
+    /// The directory mods may load their dependencies from by path or `file://` uri.
+    /// Without one, mods only depend on mods the host loaded
+    pub mod_directory: Option<PathBuf>,
+}
 
 impl Plugin for ModLoaderPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Mods>()
-            .add_systems(Update, handle_loading_mods);
+        // Plugins can't fail to build, so a bad config is reported by every load instead
+        let engine = Engine::new(&self.engine).map_err(|err| {
+            error!("Failed to create mod engine:\n{:?}", err);
+            Arc::new(err)
+        });
+
+        let (trap_sender, trap_receiver) = async_channel::unbounded();
+        app.insert_resource(Mods {
+            engine,
+            trust: self.trust.clone(),
+            permissions: self.permissions.clone(),
+            host_functions: self.host_functions.clone(),
+            schedules: self.schedules.clone(),
+            budgets: self.budgets.clone(),
+            execution: self.execution,
+            mod_directory: self.mod_directory.clone(),
+            loading: Vec::new(),
+            waiting: Vec::new(),
+            slots: Vec::new(),
+            unloaded: Vec::new(),
+            unregistered: Vec::new(),
+            added_systems: AddedSystems::default(),
+            assets: HashMap::new(),
+            trap_sender,
+            trap_receiver,
+        })
+        .add_event::<ModLoaded>()
+        .add_event::<ModLoadFailed>()
+        .add_event::<ModUnloaded>()
+        .add_event::<ModReloaded>()
+        .add_event::<ModTrapped>()
+        .add_event::<ModSuspended>()
+        .add_systems(Update, handle_loading_mods)
+        .add_systems(First, register_mod_systems)
+        .add_systems(Last, report_mod_system_traps);
+
+        if app.world().contains_resource::<AssetServer>() {
+            app.init_asset::<ModAsset>()
+                .init_asset_loader::<ModAssetLoader>()
+                .add_systems(Update, handle_mod_assets.before(handle_loading_mods));
+        }
+
+        if self.execution == ExecutionMode::Parallel {
+            for (id, label) in self.schedules.iter() {
+                let system = run_mod_schedule(id.clone());
+                if label == Update.intern() {
+                    // Mods loaded this update run right away
+                    app.add_systems(label, system.after(handle_loading_mods));
+                } else {
+                    app.add_systems(label, system);
+                }
+            }
+        }
     }
 }
 
-#[derive(Resource, Default)]
+/// Identifies a mod across reloads. Returned by every load call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModHandle(pub(crate) u32);
+
+/// The load status of a mod, see [`Mods::status`]
+#[derive(Debug)]
+pub enum ModStatus<'a> {
+    /// The mod is being loaded or reloaded
+    Loading,
+    /// The mod is running
+    Loaded,
+    /// The mod failed to load
+    ///
+    /// When a reload fails, the previous version keeps running and is reported as loaded
+    Failed(&'a Error),
+    /// The mod went over its execution budget, and doesn't run until it is resumed
+    Suspended,
+    /// The mod was unloaded, and no longer runs
+    Unloaded,
+}
+
+/// A mod known to [`Mods`], whether or not it is loaded
+struct ModSlot {
+    name: String,
+    loaded: Option<LoadedMod>,
+    /// The error raised by the last attempt at loading the mod
+    error: Option<Error>,
+}
+
+#[derive(Resource)]
 pub struct Mods {
-    engine: Engine,
-    loading: Vec<Task<Result<LoadedMod>>>,
-    loaded: Vec<Option<LoadedMod>>,
+    engine: std::result::Result<Engine, Arc<Error>>,
+    trust: TrustStore,
+    permissions: ModPermissions,
+    host_functions: HostFunctions,
+    schedules: ModSchedules,
+    budgets: ModBudgets,
+    loading: Vec<(ModHandle, Task<Result<LoadedMod>>)>,
+    /// Loaded mods that don't start until the mods they depend on do
+    waiting: Vec<(ModHandle, LoadedMod)>,
+    /// Every mod ever loaded, indexed by handle
+    slots: Vec<ModSlot>,
+    /// Mods unloaded since the last update, with the entities they spawned
+    unloaded: Vec<(ModHandle, Vec<Entity>)>,
+    execution: ExecutionMode,
+    mod_directory: Option<PathBuf>,
+    /// Mods loaded since the last update whose bevy systems are yet to be added
+    unregistered: Vec<ModHandle>,
+    added_systems: AddedSystems,
+    /// The mods loaded from each [`ModAsset`]
+    pub(crate) assets: HashMap<AssetId<ModAsset>, ModHandle>,
+    /// Traps of mod systems run as bevy systems
+    trap_sender: Sender<(ModHandle, SystemTrap)>,
+    trap_receiver: Receiver<(ModHandle, SystemTrap)>,
 }
 
 impl Mods {
-    pub fn load_from_path<P>(&mut self, path: P)
+    /// Starts loading a mod from a path
+    ///
+    /// Mods are identified by their package name, so loading a mod that is already
+    /// loaded reloads it and returns the same handle
+    pub fn load_from_path<P>(&mut self, path: P) -> ModHandle
     where
         P: AsRef<Path>,
     {
-        let engine = self.engine.clone();
+        let settings = self.load_settings();
         let path = path.as_ref().to_owned();
-        self.enque_loading(LoadedMod::try_from_path(engine, path))
+        let handle = self.handle_for(package_name(&path));
+        self.enque_loading(handle, async move {
+            LoadedMod::try_from_path(settings?, path).await
+        });
+        handle
+    }
+
+    /// Starts loading a mod from the files of a [`ModAsset`]
+    ///
+    /// Mods loaded through the asset server are handled automatically, so this is only
+    /// needed to load a mod from an asset more than once
+    pub fn load_from_asset(&mut self, asset: &ModAsset) -> ModHandle {
+        let settings = self.load_settings();
+        let name = asset.name.clone();
+        let package = asset.package.clone();
+        let handle = self.handle_for(name.clone());
+        self.enque_loading(handle, async move {
+            LoadedMod::try_from_package(&settings?, name.clone(), package)
+                .await
+                .with_context(|| format!("Failed to load mod from asset: {}", name))
+        });
+        handle
+    }
+
+    pub fn status(&self, handle: ModHandle) -> ModStatus<'_> {
+        if self.is_pending(handle) {
+            return ModStatus::Loading;
+        }
+
+        match self.slots.get(handle.0 as usize) {
+            Some(ModSlot {
+                loaded: Some(loaded),
+                ..
+            }) if loaded.is_suspended() => ModStatus::Suspended,
+            Some(ModSlot {
+                loaded: Some(_), ..
+            }) => ModStatus::Loaded,
+            Some(ModSlot {
+                error: Some(error), ..
+            }) => ModStatus::Failed(error),
+            _ => ModStatus::Unloaded,
+        }
+    }
+
+    /// Returns the handle of the mod loaded from the given asset
+    pub fn handle_of_asset(&self, asset: impl Into<AssetId<ModAsset>>) -> Option<ModHandle> {
+        self.assets.get(&asset.into()).copied()
+    }
+
+    pub fn get(&self, handle: ModHandle) -> Option<&LoadedMod> {
+        self.slots.get(handle.0 as usize)?.loaded.as_ref()
+    }
+
+    /// Iterates over every loaded mod
+    pub fn iter(&self) -> impl Iterator<Item = (ModHandle, &LoadedMod)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let loaded = slot.loaded.as_ref()?;
+            Some((ModHandle(index as u32), loaded))
+        })
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (ModHandle, &mut LoadedMod)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let loaded = slot.loaded.as_mut()?;
+                Some((ModHandle(index as u32), loaded))
+            })
+    }
+
+    /// Unloads a mod, tearing down its instance, schedules and resources. Loads in
+    /// progress are cancelled
+    ///
+    /// Entities spawned by the mod are despawned on the next update
+    pub fn unload(&mut self, handle: ModHandle) -> Result<()> {
+        let slot = self
+            .slots
+            .get_mut(handle.0 as usize)
+            .ok_or_else(|| anyhow!("Unknown mod handle {:?}", handle))?;
+
+        // Dropping a task cancels it
+        let pending = self.loading.len() + self.waiting.len();
+        self.loading.retain(|(other, _)| *other != handle);
+        self.waiting.retain(|(other, _)| *other != handle);
+        let cancelled = pending != self.loading.len() + self.waiting.len();
+
+        slot.error = None;
+        match slot.loaded.take() {
+            Some(mut loaded) => {
+                self.unloaded.push((handle, loaded.take_entities()));
+                info!("Mod unloaded: {}", slot.name);
+            }
+            None if cancelled => {
+                info!("Mod load cancelled: {}", slot.name);
+            }
+            None => bail!("Mod {:?} is not loaded", slot.name),
+        }
+
+        Ok(())
+    }
+
+    /// Changes the execution budget of a mod, including future versions of it
+    pub fn set_budget(&mut self, handle: ModHandle, budget: ExecutionBudget) -> Result<()> {
+        let slot = self
+            .slots
+            .get_mut(handle.0 as usize)
+            .ok_or_else(|| anyhow!("Unknown mod handle {:?}", handle))?;
+
+        self.budgets.set(slot.name.clone(), budget);
+        if let Some(loaded) = slot.loaded.as_mut() {
+            loaded.set_budget(budget);
+        }
+
+        Ok(())
+    }
+
+    /// Resumes a mod suspended for going over its budget
+    pub fn resume(&mut self, handle: ModHandle) -> Result<()> {
+        let loaded = self
+            .slots
+            .get_mut(handle.0 as usize)
+            .and_then(|slot| slot.loaded.as_mut())
+            .ok_or_else(|| anyhow!("Mod {:?} is not loaded", handle))?;
+        if !loaded.is_suspended() {
+            bail!("Mod {:?} is not suspended", loaded.name());
+        }
+
+        loaded.resume();
+        info!("Mod resumed: {}", loaded.name());
+
+        Ok(())
     }
 
-    fn enque_loading(&mut self, future: impl Future<Output = Result<LoadedMod>> + Send + 'static) {
+    /// Starts or stops running the systems of a feature of a mod
+    ///
+    /// The resources of a disabled feature keep their values, see [`Mods::reset_feature`].
+    /// Whether a feature is enabled carries over to future versions of the mod
+    pub fn set_feature_enabled(
+        &mut self,
+        handle: ModHandle,
+        feature: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        let loaded = self
+            .slots
+            .get_mut(handle.0 as usize)
+            .and_then(|slot| slot.loaded.as_mut())
+            .ok_or_else(|| anyhow!("Mod {:?} is not loaded", handle))?;
+
+        loaded.set_feature_enabled(feature, enabled)?;
+        let state = if enabled { "enabled" } else { "disabled" };
+        info!("Feature {} of mod {} {}", feature, loaded.name(), state);
+
+        Ok(())
+    }
+
+    /// Resets the resources of a feature of a mod to their default values
+    pub fn reset_feature(&mut self, handle: ModHandle, feature: &str) -> Result<()> {
+        let loaded = self
+            .slots
+            .get_mut(handle.0 as usize)
+            .and_then(|slot| slot.loaded.as_mut())
+            .ok_or_else(|| anyhow!("Mod {:?} is not loaded", handle))?;
+
+        loaded.reset_feature(feature)
+    }
+
+    /// Reloads a mod from the path it was loaded from
+    ///
+    /// Once loaded, the new version replaces the old one and inherits its state
+    pub fn reload(&mut self, handle: ModHandle) -> Result<ModHandle> {
+        let loaded = self
+            .get(handle)
+            .ok_or_else(|| anyhow!("Mod {:?} is not loaded", handle))?;
+        let path = loaded
+            .source
+            .clone()
+            .ok_or_else(|| anyhow!("Mod {:?} was not loaded from a path", loaded.name()))?;
+
+        Ok(self.load_from_path(path))
+    }
+
+    /// Whether the mod is loading, or waiting on its dependencies to start
+    fn is_pending(&self, handle: ModHandle) -> bool {
+        self.loading.iter().any(|(other, _)| *other == handle)
+            || self.waiting.iter().any(|(other, _)| *other == handle)
+    }
+
+    /// Returns the handle of the mod with the given package name, if it is known
+    fn find_handle(&self, name: &str) -> Option<ModHandle> {
+        let index = self.slots.iter().position(|slot| slot.name == name)?;
+        Some(ModHandle(index as u32))
+    }
+
+    /// Resolves the dependencies of the waiting mod at the given index
+    ///
+    /// Dependencies given by path or `file://` uri that aren't known yet start loading from
+    /// the mod directory, if the host configured one
+    fn resolve_dependencies(&mut self, index: usize) -> Resolution {
+        let (_, loaded) = &self.waiting[index];
+        let dependencies = loaded.dependencies().to_vec();
+
+        let mut resolution = Resolution::Started;
+        let mut unresolved = Vec::new();
+        for dependency in dependencies {
+            let name = dependency_name(&dependency.id);
+            let Some(handle) = self.find_handle(&name) else {
+                let path = self
+                    .mod_directory
+                    .as_deref()
+                    .and_then(|directory| dependency_path(&dependency.id, directory));
+                match path {
+                    Some(path) => {
+                        self.load_from_path(path);
+                        resolution = Resolution::Pending;
+                    }
+                    None => unresolved.push(format!("{} {}", dependency.id, dependency.version)),
+                }
+                continue;
+            };
+
+            match self.get(handle) {
+                Some(loaded) if !satisfies(&dependency, loaded.version()) => {
+                    unresolved.push(format!(
+                        "{} {} (found {})",
+                        dependency.id,
+                        dependency.version,
+                        loaded.version()
+                    ))
+                }
+                Some(_) => {}
+                None if self.is_pending(handle) => resolution = Resolution::Pending,
+                None => unresolved.push(format!("{} {}", dependency.id, dependency.version)),
+            }
+        }
+
+        // Pending dependencies are waited on, so the error lists every one that's unresolved
+        if unresolved.is_empty() || matches!(resolution, Resolution::Pending) {
+            return resolution;
+        }
+        Resolution::Unresolved(anyhow!(
+            "Mod {} has unresolved dependencies: {}",
+            self.waiting[index].1.name(),
+            unresolved.join(", ")
+        ))
+    }
+
+    /// Returns the waiting mods that can never start, because they depend on each other
+    fn dependency_cycles(&self) -> Vec<ModHandle> {
+        // Mods that may start once the mods still loading are done
+        let mut progressing: HashSet<_> = self.loading.iter().map(|(handle, _)| *handle).collect();
+        loop {
+            let mut changed = false;
+            for (handle, loaded) in self.waiting.iter() {
+                if progressing.contains(handle) {
+                    continue;
+                }
+                let waits_on_progress = loaded.dependencies().iter().any(|dependency| {
+                    self.find_handle(&dependency_name(&dependency.id))
+                        .is_some_and(|dependency| progressing.contains(&dependency))
+                });
+                if waits_on_progress {
+                    progressing.insert(*handle);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        self.waiting
+            .iter()
+            .map(|(handle, _)| *handle)
+            .filter(|handle| !progressing.contains(handle))
+            .collect()
+    }
+
+    fn load_settings(&self) -> Result<LoadSettings> {
+        let engine = self
+            .engine
+            .clone()
+            .map_err(|err| anyhow!("The mod engine failed to start: {:#}", err))?;
+        Ok(LoadSettings {
+            engine,
+            trust: self.trust.clone(),
+            permissions: self.permissions.clone(),
+            schedules: self.schedules.clone(),
+            host_functions: self.host_functions.clone(),
+            budgets: self.budgets.clone(),
+            execution: self.execution,
+        })
+    }
+
+    /// Returns the handle of the mod with the given package name, creating it if needed
+    fn handle_for(&mut self, name: String) -> ModHandle {
+        let index = match self.slots.iter().position(|slot| slot.name == name) {
+            Some(index) => index,
+            None => {
+                self.slots.push(ModSlot {
+                    name,
+                    loaded: None,
+                    error: None,
+                });
+                self.slots.len() - 1
+            }
+        };
+        ModHandle(index as u32)
+    }
+
+    fn enque_loading(
+        &mut self,
+        handle: ModHandle,
+        future: impl Future<Output = Result<LoadedMod>> + Send + 'static,
+    ) {
         let thread_pool = AsyncComputeTaskPool::get();
         let task = thread_pool.spawn(future);
-        self.loading.push(task);
+        self.loading.push((handle, task));
     }
 }
 
-fn handle_loading_mods(mut mods: ResMut<Mods>) {
+fn handle_loading_mods(
+    mut mods: ResMut<Mods>,
+    mut commands: Commands,
+    mut events: ModEventWriters,
+) {
     // Remove loaded tasks from loading
     let mut loaded = Vec::new();
-    mods.loading.retain_mut(|task| {
+    mods.loading.retain_mut(|(handle, task)| {
         if let Some(result) = block_on(poll_once(task)) {
-            loaded.push(result);
+            loaded.push((*handle, result));
             false
         } else {
             true
         }
     });
 
-    for loaded in loaded {
+    for (handle, entities) in mods.unloaded.drain(..) {
+        for entity in entities {
+            commands.entity(entity).try_despawn();
+        }
+        events.unloaded.write(ModUnloaded { handle });
+    }
+
+    let mods = &mut *mods;
+    for (handle, loaded) in loaded {
         match loaded {
-            Result::Ok(loaded) => {
-                let opt = Some(loaded);
-                if mods.loaded.contains(&opt) {
-                    warn!(
-                        "Mod already loaded: {:#?}. Skipping.",
-                        opt.as_ref().unwrap().manifest_hash
-                    );
-                } else {
-                    info!("Mod loaded: {:#?}", opt.as_ref().unwrap());
-                    mods.loaded.push(opt);
+            // When two versions of a mod finish loading together, the newer one is kept
+            Result::Ok(loaded) => match mods.waiting.iter_mut().find(|(other, _)| *other == handle)
+            {
+                Some((_, waiting)) if loaded.version() < waiting.version() => {}
+                Some((_, waiting)) => *waiting = loaded,
+                None => mods.waiting.push((handle, loaded)),
+            },
+            Err(err) => fail_mod(mods, handle, err, &mut events),
+        }
+    }
+
+    // A mod starts once every mod it depends on has, so mods start in topological order
+    let mut index = 0;
+    while index < mods.waiting.len() {
+        match mods.resolve_dependencies(index) {
+            Resolution::Pending => index += 1,
+            resolution => {
+                let (handle, loaded) = mods.waiting.remove(index);
+                match resolution {
+                    Resolution::Unresolved(err) => fail_mod(mods, handle, err, &mut events),
+                    _ => start_mod(mods, handle, loaded, &mut commands, &mut events),
                 }
+                // Starting or failing a mod may resolve the mods before it
+                index = 0;
+            }
+        }
+    }
+
+    for handle in mods.dependency_cycles() {
+        let Some(position) = mods.waiting.iter().position(|(other, _)| *other == handle) else {
+            continue;
+        };
+        let (_, loaded) = mods.waiting.remove(position);
+        let err = anyhow!("Mod {} is part of a dependency cycle", loaded.name());
+        fail_mod(mods, handle, err, &mut events);
+    }
+}
+
+/// How far the dependencies of a mod are from starting
+enum Resolution {
+    /// Every dependency started
+    Started,
+    /// Some dependencies are still loading
+    Pending,
+    /// Some dependencies can't be found, or failed to load
+    Unresolved(Error),
+}
+
+/// The package name of a mod depended on by name or uri
+fn dependency_name(id: &str) -> String {
+    let path = id.split_once("://").map_or(id, |(_, path)| path);
+    package_name(Path::new(path))
+}
+
+/// Whether a version of a mod satisfies the requirement of a mod depending on it
+fn satisfies(dependency: &Dependency, version: &semver::Version) -> bool {
+    semver::VersionReq::parse(&dependency.version).is_ok_and(|req| req.matches(version))
+}
+
+/// The path a dependency can be loaded from, if it is given by path or `file://` uri to a
+/// file inside the mod directory
+///
+/// Manifests aren't trusted, so paths leading out of the directory are rejected
+fn dependency_path(id: &str, directory: &Path) -> Option<PathBuf> {
+    let path = match id.split_once("://") {
+        Some(("file", path)) => Path::new(path),
+        // Fetching mods from elsewhere is not supported
+        Some(_) => return None,
+        None => {
+            let path = Path::new(id);
+            let is_package = path
+                .extension()
+                .is_some_and(|ext| ext == common::ModPackage::EXTENSION);
+            if !is_package && !id.contains(['/', '\\']) {
+                return None;
             }
+            path
+        }
+    };
+    let directory = directory.canonicalize().ok()?;
+    let path = directory.join(path).canonicalize().ok()?;
+    path.starts_with(&directory).then_some(path)
+}
+
+fn start_mod(
+    mods: &mut Mods,
+    handle: ModHandle,
+    mut loaded: LoadedMod,
+    commands: &mut Commands,
+    events: &mut ModEventWriters,
+) {
+    let slot = &mut mods.slots[handle.0 as usize];
+    if let Some(previous) = slot.loaded.as_ref() {
+        if previous.version() > loaded.version() {
+            let err = anyhow!(
+                "Mod {} {} is older than the running version {}, which is kept",
+                loaded.name(),
+                loaded.version(),
+                previous.version()
+            );
+            fail_mod(mods, handle, err, events);
+            return;
+        }
+    }
+    loaded.set_budget(mods.budgets.get(loaded.name()));
+
+    if let Some(previous) = slot.loaded.as_mut() {
+        // A new version of the mod replaces the old one. Its state carries over,
+        // so startup systems don't run again
+        let changes = ManifestDiff::new(previous.manifest(), loaded.manifest());
+        if changes.is_breaking() {
+            warn!(
+                "Mod {} {} has breaking changes from {}:\n{}",
+                loaded.name(),
+                loaded.version(),
+                previous.version(),
+                changes
+            );
+        }
+
+        let report = match loaded.migrate_from(previous) {
+            Result::Ok(report) => report,
             Err(err) => {
-                error!("Failed to load mod:\n{:?}", err);
+                let err = err.context(format!(
+                    "Failed to migrate state of mod {} {}, the running version {} is kept",
+                    loaded.name(),
+                    loaded.version(),
+                    previous.version()
+                ));
+                fail_mod(mods, handle, err, events);
+                return;
             }
+        };
+        info!("Mod reloaded: {}\n{:#?}", loaded.name(), report);
+        events.reloaded.write(ModReloaded {
+            handle,
+            report,
+            changes,
+        });
+    } else {
+        info!("Mod loaded: {:#?}", loaded);
+
+        // Startup systems run once, before the mod's first update
+        let traps = startup_schedules()
+            .iter()
+            .flat_map(|id| loaded.run_schedule(id, commands))
+            .collect();
+        report_traps(
+            handle,
+            &loaded,
+            traps,
+            &mut events.trapped,
+            &mut events.suspended,
+        );
+        events.loaded.write(ModLoaded { handle });
+    }
+
+    let slot = &mut mods.slots[handle.0 as usize];
+    slot.error = None;
+    if mods.execution == ExecutionMode::BevySystems {
+        mods.unregistered.push(handle);
+    }
+    slot.loaded = Some(loaded);
+}
+
+fn fail_mod(mods: &mut Mods, handle: ModHandle, err: Error, events: &mut ModEventWriters) {
+    error!("Failed to load mod:\n{:?}", err);
+    events.load_failed.write(ModLoadFailed {
+        handle,
+        error: format!("{:#}", err),
+    });
+    mods.slots[handle.0 as usize].error = Some(err);
+}
+
+/// Returns a system running the schedule with the given id of every loaded mod
+fn run_mod_schedule(
+    id: StableId,
+) -> impl FnMut(ResMut<Mods>, ParallelCommands, EventWriter<ModTrapped>, EventWriter<ModSuspended>)
+{
+    move |mut mods, commands, mut trapped, mut suspended| {
+        let results = run_schedule_in_parallel(&mut mods, &id, &commands);
+        for (handle, traps) in results {
+            let loaded = mods.get(handle).unwrap();
+            report_traps(handle, loaded, traps, &mut trapped, &mut suspended);
+        }
+    }
+}
+
+/// Runs a schedule of every loaded mod on the [`ComputeTaskPool`]
+///
+/// Each mod runs in its own store and only reaches the world through commands, so mods
+/// never conflict and all of them run at once. Systems of a single mod share its store, so
+/// they run one at a time, in dependency order
+fn run_schedule_in_parallel(
+    mods: &mut Mods,
+    id: &StableId,
+    commands: &ParallelCommands,
+) -> Vec<(ModHandle, Vec<SystemTrap>)> {
+    ComputeTaskPool::get_or_init(TaskPool::default).scope(|scope| {
+        for (handle, loaded) in mods.iter_mut() {
+            scope.spawn(async move {
+                let traps =
+                    commands.command_scope(|mut commands| loaded.run_schedule(id, &mut commands));
+                (handle, traps)
+            });
+        }
+    })
+}
+
+/// Adds the systems of newly loaded mods to the host's schedules
+///
+/// Runs before the schedules mod systems are added to, since running schedules can't be
+/// changed
+fn register_mod_systems(world: &mut World) {
+    world.resource_scope(|world, mut mods: Mut<Mods>| {
+        let mods = &mut *mods;
+        let mut schedules = world.resource_mut::<Schedules>();
+        for handle in mods.unregistered.drain(..) {
+            // The mod may have been unloaded since
+            let Some(loaded) = mods.slots[handle.0 as usize].loaded.as_ref() else {
+                continue;
+            };
+            add_mod_systems(
+                &mut schedules,
+                &mut mods.added_systems,
+                handle,
+                loaded,
+                &mods.schedules,
+                &mods.trap_sender,
+            );
         }
+    });
+}
+
+fn report_mod_system_traps(
+    mods: Res<Mods>,
+    mut trapped: EventWriter<ModTrapped>,
+    mut suspended: EventWriter<ModSuspended>,
+) {
+    while let Result::Ok((handle, trap)) = mods.trap_receiver.try_recv() {
+        let Some(loaded) = mods.get(handle) else {
+            continue;
+        };
+        report_traps(handle, loaded, vec![trap], &mut trapped, &mut suspended);
+    }
+}
+
+fn report_traps(
+    handle: ModHandle,
+    loaded: &LoadedMod,
+    traps: Vec<SystemTrap>,
+    trapped: &mut EventWriter<ModTrapped>,
+    suspended: &mut EventWriter<ModSuspended>,
+) {
+    let Some(last) = traps.last() else {
+        return;
+    };
+    // Only the last system can suspend the mod, since no system runs after that
+    let suspended_by = loaded.is_suspended().then(|| last.system.clone());
+
+    for SystemTrap { system, error } in traps {
+        error!("Mod system {} trapped:\n{:?}", system, error);
+        trapped.write(ModTrapped {
+            handle,
+            system,
+            message: format!("{:#}", error),
+        });
+    }
+
+    if let Some(system) = suspended_by {
+        warn!(
+            "Mod {} suspended after system {} went over budget",
+            loaded.name(),
+            system
+        );
+        suspended.write(ModSuspended { handle, system });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::TaskPoolPlugin;
+    use bevy_asset::{AssetPlugin, Assets};
+    use bevy_ecs::event::Events;
+
+    use common::SystemId;
+
+    use super::*;
+    use crate::{engine::EngineSettings, loaded};
+
+    #[test]
+    fn assets_require_asset_plugin() {
+        let mut app = App::new();
+        app.add_plugins(ModLoaderPlugin::default());
+        app.update();
+        assert!(!app.world().contains_resource::<Assets<ModAsset>>());
+
+        let mut app = App::new();
+        app.add_plugins((
+            TaskPoolPlugin::default(),
+            AssetPlugin::default(),
+            ModLoaderPlugin::default(),
+        ));
+        app.update();
+        assert!(app.world().contains_resource::<Assets<ModAsset>>());
+    }
+
+    /// A mod whose base spawns an entity every run, and whose `extra` feature spawns one
+    /// the first time it runs after its counter was reset
+    fn features_mod() -> LoadedMod {
+        let counter = StableId::new("test", "Counter");
+        let mut extra = loaded::tests::feature("extra", &[(SystemId::of::<[u8; 1]>(), "extra")]);
+        extra.resources = vec![(counter.clone(), vec![0])];
+        let mut manifest = loaded::tests::manifest(vec![
+            loaded::tests::feature("features", &[(SystemId::of::<[u8; 0]>(), "base")]),
+            extra,
+        ]);
+        manifest.capabilities = vec![common::Capability::SpawnEntities];
+        manifest.types = vec![common::TypeSignature::Struct {
+            ty: counter,
+            size: Some(4),
+            align: Some(4),
+            generics: vec![],
+            fields: vec![common::FieldSignature {
+                name: "count".to_owned(),
+                ty: StableId::new("unknown", "u32"),
+            }],
+            layout: vec![common::FieldLayout { offset: 0, size: 4 }],
+        }];
+
+        let (_, address) = common::type_addresses(&manifest.types).unwrap()[0].clone();
+        let wat = format!(
+            r#"(module
+                (import "bevy_harmonize" "spawn_empty" (func $spawn (result i32)))
+                (import "bevy" "test::Counter" (memory $counter 0 (pagesize 1)))
+                (memory (export "memory") 1)
+                (func (export "run") (param i32) (result i32)
+                    local.get 0
+                    if
+                        i32.const {address}
+                        i32.load $counter
+                        i32.eqz
+                        if
+                            call $spawn
+                            drop
+                        end
+                        i32.const {address}
+                        i32.const 1
+                        i32.store $counter
+                    else
+                        call $spawn
+                        drop
+                    end
+                    i32.const 0))"#,
+            address = address.start
+        );
+        let settings = loaded::tests::settings();
+        loaded::tests::load(&settings, "features", manifest, &wat).unwrap()
+    }
+
+    fn mods(app: &mut App) -> Mut<'_, Mods> {
+        app.world_mut().resource_mut::<Mods>()
+    }
+
+    fn toggle_features(execution: ExecutionMode) {
+        let mut app = App::new();
+        app.add_plugins((
+            TaskPoolPlugin::default(),
+            ModLoaderPlugin {
+                execution,
+                engine: EngineSettings {
+                    cache: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ));
+        let handle = mods(&mut app).handle_for("features".to_owned());
+        let loaded = features_mod();
+        mods(&mut app).enque_loading(handle, async move { Ok(loaded) });
+        for _ in 0..100 {
+            if app.world().resource::<Mods>().get(handle).is_some() {
+                break;
+            }
+            app.update();
+        }
+        // Until the systems of every feature ran once
+        app.update();
+        app.update();
+
+        let spawned = |app: &mut App| {
+            let before = app.world().entities().len();
+            app.update();
+            app.world().entities().len() - before
+        };
+        assert_eq!(spawned(&mut app), 1);
+
+        mods(&mut app).reset_feature(handle, "extra").unwrap();
+        assert_eq!(spawned(&mut app), 2);
+        assert_eq!(spawned(&mut app), 1);
+
+        mods(&mut app)
+            .set_feature_enabled(handle, "extra", false)
+            .unwrap();
+        mods(&mut app).reset_feature(handle, "extra").unwrap();
+        assert_eq!(spawned(&mut app), 1);
+
+        mods(&mut app)
+            .set_feature_enabled(handle, "extra", true)
+            .unwrap();
+        assert_eq!(spawned(&mut app), 2);
+
+        assert!(mods(&mut app).reset_feature(handle, "unknown").is_err());
+    }
+
+    #[test]
+    fn features_toggle_in_parallel() {
+        toggle_features(ExecutionMode::Parallel);
+    }
+
+    #[test]
+    fn features_toggle_as_bevy_systems() {
+        toggle_features(ExecutionMode::BevySystems);
+    }
+
+    #[test]
+    fn dependencies_resolve_by_name_or_uri() {
+        assert_eq!(dependency_name("physics"), "physics");
+        assert_eq!(dependency_name("mods/physics.hmod"), "physics");
+        assert_eq!(dependency_name("file://mods/physics.hmod"), "physics");
+        assert_eq!(
+            dependency_name("https://example.com/physics.hmod"),
+            "physics"
+        );
+    }
+
+    #[test]
+    fn dependency_paths_stay_in_the_mod_directory() {
+        let root = std::env::temp_dir().join(format!(
+            "bevy_harmonize_dependencies_{}",
+            std::process::id()
+        ));
+        let directory = root.join("mods");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("physics.hmod"), []).unwrap();
+        std::fs::write(root.join("outside.hmod"), []).unwrap();
+
+        let physics = directory.canonicalize().unwrap().join("physics.hmod");
+        assert_eq!(dependency_path("physics", &directory), None);
+        assert_eq!(
+            dependency_path("physics.hmod", &directory),
+            Some(physics.clone())
+        );
+        assert_eq!(
+            dependency_path("file://physics.hmod", &directory),
+            Some(physics)
+        );
+        assert_eq!(dependency_path("missing.hmod", &directory), None);
+        assert_eq!(
+            dependency_path("https://example.com/physics.hmod", &directory),
+            None
+        );
+
+        let outside = root.join("outside.hmod").canonicalize().unwrap();
+        assert_eq!(dependency_path("../outside.hmod", &directory), None);
+        assert_eq!(dependency_path(outside.to_str().unwrap(), &directory), None);
+        assert_eq!(
+            dependency_path(&format!("file://{}", outside.display()), &directory),
+            None
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    /// An app with loaded mods waiting to start, each depending on mods by name
+    fn waiting_mods(waiting: &[(&str, &[(&str, &str)])]) -> (App, Vec<ModHandle>) {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), ModLoaderPlugin::default()));
+
+        let settings = loaded::tests::settings();
+        let handles = waiting
+            .iter()
+            .map(|(name, dependencies)| {
+                let mut manifest = loaded::tests::manifest(vec![loaded::tests::feature(name, &[])]);
+                manifest.dependencies = dependencies
+                    .iter()
+                    .map(|(id, version)| Dependency {
+                        id: (*id).to_owned(),
+                        version: (*version).to_owned(),
+                    })
+                    .collect();
+                let loaded =
+                    loaded::tests::load(&settings, name, manifest, loaded::tests::BUSY_WAT)
+                        .unwrap();
+                let mut mods = mods(&mut app);
+                let handle = mods.handle_for((*name).to_owned());
+                mods.waiting.push((handle, loaded));
+                handle
+            })
+            .collect();
+        (app, handles)
+    }
+
+    fn load_errors(app: &App) -> Vec<(ModHandle, String)> {
+        app.world()
+            .resource::<Events<ModLoadFailed>>()
+            .iter_current_update_events()
+            .map(|event| (event.handle, event.error.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn dependencies_start_first() {
+        let (mut app, handles) = waiting_mods(&[
+            ("game", &[("physics", "^0.1"), ("render", "*")]),
+            ("physics", &[("math", "*")]),
+            ("render", &[("math", "*")]),
+            ("math", &[]),
+        ]);
+        app.update();
+
+        let started: Vec<_> = app
+            .world()
+            .resource::<Events<ModLoaded>>()
+            .iter_current_update_events()
+            .map(|event| event.handle)
+            .collect();
+        assert_eq!(started, [handles[3], handles[1], handles[2], handles[0]]);
+        assert!(load_errors(&app).is_empty());
+    }
+
+    #[test]
+    fn dependency_cycles_fail_to_start() {
+        let (mut app, handles) = waiting_mods(&[
+            ("a", &[("b", "*")]),
+            ("b", &[("a", "*")]),
+            ("c", &[("a", "*")]),
+            ("d", &[]),
+        ]);
+        app.update();
+
+        let errors = load_errors(&app);
+        let failed: HashSet<_> = errors.iter().map(|(handle, _)| *handle).collect();
+        assert_eq!(failed, HashSet::from_iter(handles[..3].iter().copied()));
+        assert!(errors
+            .iter()
+            .any(|(handle, error)| *handle == handles[0] && error.contains("dependency cycle")));
+        assert!(mods(&mut app).get(handles[3]).is_some());
+    }
+
+    #[test]
+    fn unresolved_dependencies_are_listed() {
+        let (mut app, handles) = waiting_mods(&[
+            (
+                "game",
+                &[("physics", "^2"), ("missing", "*"), ("math", "*")],
+            ),
+            ("physics", &[]),
+            ("math", &[]),
+        ]);
+        app.update();
+
+        let errors = load_errors(&app);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, handles[0]);
+        assert!(errors[0].1.contains("physics ^2 (found 0.1.0), missing *"));
+        assert!(!errors[0].1.contains("math"));
+    }
+
+    #[test]
+    fn dependencies_require_versions() {
+        let dependency = |version: &str| Dependency {
+            id: "physics".to_owned(),
+            version: version.to_owned(),
+        };
+        let version = semver::Version::new(1, 4, 2);
+
+        assert!(satisfies(&dependency("^1.2"), &version));
+        assert!(satisfies(&dependency("*"), &version));
+        assert!(!satisfies(&dependency("^2"), &version));
+        assert!(!satisfies(&dependency("not a requirement"), &version));
     }
 }
@@ -0,0 +1,108 @@
+use anyhow::*;
+use bevy_platform::collections::HashMap;
+use common::Capability;
+
+/// The capabilities granted to mods, see [`crate::prelude::ModLoaderPlugin`]
+///
+/// Only [`Capability::SpawnEntities`] is granted to every mod by default, so mods requiring
+/// any other capability are refused. Use [`ModPermissions::none`] to grant nothing
+#[derive(Debug, Clone)]
+pub struct ModPermissions {
+    /// Capabilities granted to every mod
+    everyone: Vec<Capability>,
+    /// Capabilities granted to single mods, keyed by package name
+    mods: HashMap<String, Vec<Capability>>,
+}
+
+impl Default for ModPermissions {
+    fn default() -> Self {
+        Self::none().with_granted_to_all(Capability::SpawnEntities)
+    }
+}
+
+impl ModPermissions {
+    /// Grants no capability to any mod
+    pub fn none() -> Self {
+        Self {
+            everyone: Vec::new(),
+            mods: HashMap::new(),
+        }
+    }
+
+    /// Grants a capability to every mod
+    pub fn with_granted_to_all(mut self, capability: Capability) -> Self {
+        self.grant_all(capability);
+        self
+    }
+
+    /// Grants a capability to the mod with the given package name
+    pub fn with_granted(mut self, name: impl Into<String>, capability: Capability) -> Self {
+        self.grant(name, capability);
+        self
+    }
+
+    pub fn grant_all(&mut self, capability: Capability) {
+        if !self.everyone.contains(&capability) {
+            self.everyone.push(capability);
+        }
+    }
+
+    pub fn grant(&mut self, name: impl Into<String>, capability: Capability) {
+        let granted = self.mods.entry(name.into()).or_default();
+        if !granted.contains(&capability) {
+            granted.push(capability);
+        }
+    }
+
+    pub fn is_granted(&self, name: &str, capability: &Capability) -> bool {
+        self.everyone.contains(capability)
+            || self
+                .mods
+                .get(name)
+                .is_some_and(|granted| granted.contains(capability))
+    }
+
+    /// Checks that every capability required by a mod is granted
+    pub(crate) fn check(&self, name: &str, required: &[Capability]) -> Result<()> {
+        let missing: Vec<_> = required
+            .iter()
+            .filter(|capability| !self.is_granted(name, capability))
+            .map(|capability| capability.to_string())
+            .collect();
+        if !missing.is_empty() {
+            bail!(
+                "Mod {} requires capabilities that were not granted: {}",
+                name,
+                missing.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_lists_missing_grants() {
+        let play_sound = Capability::HostFunction("play_sound".to_owned());
+        let permissions = ModPermissions::none()
+            .with_granted_to_all(play_sound.clone())
+            .with_granted("trusted", Capability::SpawnEntities);
+
+        let required = [play_sound, Capability::SpawnEntities];
+        assert!(permissions.check("trusted", &required).is_ok());
+
+        let err = permissions.check("other", &required).unwrap_err();
+        assert!(err.to_string().contains("SpawnEntities"));
+        assert!(!err.to_string().contains("play_sound"));
+    }
+
+    #[test]
+    fn entities_can_be_spawned_by_default() {
+        let permissions = ModPermissions::default();
+        assert!(permissions.is_granted("any", &Capability::SpawnEntities));
+        assert!(!ModPermissions::none().is_granted("any", &Capability::SpawnEntities));
+    }
+}
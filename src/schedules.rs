@@ -0,0 +1,183 @@
+use bevy_app::{First, FixedUpdate, Last, Main, PostUpdate, PreUpdate, Update};
+use bevy_ecs::schedule::{InternedScheduleLabel, InternedSystemSet, ScheduleLabel, SystemSet};
+use bevy_reflect::Typed;
+use common::StableId;
+
+/// The schedule labels mods may add systems to, and the system sets they may order their
+/// systems around, see [`crate::prelude::ModLoaderPlugin`]
+///
+/// Mods name schedules by the [`StableId`] of their label type. The labels of `common` are
+/// always available: [`common::PreStart`], [`common::Start`] and [`common::PostStart`] run
+/// once when a mod is loaded, while the others run in the bevy schedule of the same name.
+/// Hosts can let mods target more bevy schedules by registering labels of their own, usually
+/// defined in a crate shared with mods
+///
+/// Mods adding systems to any other schedule fail to load
+///
+/// Sets published by the host work the same way: a mod ordering its systems around the set
+/// with a given id is ordered around the matching bevy set. This only works for mod systems
+/// run as bevy systems, see [`crate::prelude::ExecutionMode::BevySystems`]. Mods ordering
+/// their systems around host sets fail to load in other modes
+#[derive(Debug, Clone)]
+pub struct ModSchedules {
+    labels: Vec<(StableId, InternedScheduleLabel)>,
+    sets: Vec<(StableId, InternedSystemSet)>,
+}
+
+impl Default for ModSchedules {
+    fn default() -> Self {
+        Self::empty()
+            .with_typed::<common::PreUpdate>(PreUpdate)
+            .with_typed::<common::Update>(Update)
+            .with_typed::<common::PostUpdate>(PostUpdate)
+            .with_typed::<common::FixedUpdate>(FixedUpdate)
+            .with_typed::<common::Last>(Last)
+    }
+}
+
+impl ModSchedules {
+    /// Only the schedules that run when mods are loaded
+    pub fn empty() -> Self {
+        Self {
+            labels: Vec::new(),
+            sets: Vec::new(),
+        }
+    }
+
+    /// Runs mod systems added to the schedule with the given id in a bevy schedule
+    ///
+    /// # Panics
+    ///
+    /// If the bevy schedule is running when mod systems are added, see [`ModSchedules::insert`]
+    pub fn with(mut self, id: StableId, label: impl ScheduleLabel) -> Self {
+        self.insert(id, label);
+        self
+    }
+
+    /// Runs mod systems added to the schedule labeled `T` in a bevy schedule
+    pub fn with_typed<T: Typed>(self, label: impl ScheduleLabel) -> Self {
+        self.with(StableId::from_typed::<T>(), label)
+    }
+
+    /// Runs mod systems added to the schedule with the given id in a bevy schedule, replacing
+    /// the bevy schedule it ran in before
+    ///
+    /// # Panics
+    ///
+    /// If the label is [`First`] or [`Main`]. Mod systems are added in [`First`], and bevy
+    /// would drop systems added to a schedule while it runs
+    pub fn insert(&mut self, id: StableId, label: impl ScheduleLabel) {
+        let label = label.intern();
+        assert!(
+            label != First.intern() && label != Main.intern(),
+            "Mod systems can't run in {:?}, which is running when they are added",
+            label
+        );
+        match self.labels.iter_mut().find(|(other, _)| *other == id) {
+            Some((_, existing)) => *existing = label,
+            None => self.labels.push((id, label)),
+        }
+    }
+
+    /// The bevy schedule that runs the mod schedule with the given id
+    pub fn get(&self, id: &StableId) -> Option<InternedScheduleLabel> {
+        self.labels
+            .iter()
+            .find(|(other, _)| other == id)
+            .map(|(_, label)| *label)
+    }
+
+    /// Whether mods may add systems to the schedule with the given id
+    pub fn contains(&self, id: &StableId) -> bool {
+        startup_schedules().contains(id) || self.get(id).is_some()
+    }
+
+    /// Iterates over the ids of mod schedules, with the bevy schedules that run them
+    pub fn iter(&self) -> impl Iterator<Item = (&StableId, InternedScheduleLabel)> {
+        self.labels.iter().map(|(id, label)| (id, *label))
+    }
+
+    /// Publishes a bevy system set to mods under the given id
+    pub fn with_set(mut self, id: StableId, set: impl SystemSet) -> Self {
+        self.insert_set(id, set);
+        self
+    }
+
+    /// Publishes a bevy system set to mods as the set named `T`
+    pub fn with_typed_set<T: Typed>(self, set: impl SystemSet) -> Self {
+        self.with_set(StableId::from_typed::<T>(), set)
+    }
+
+    /// Publishes a bevy system set to mods under the given id, replacing the set published
+    /// under it before
+    pub fn insert_set(&mut self, id: StableId, set: impl SystemSet) {
+        let set = set.intern();
+        match self.sets.iter_mut().find(|(other, _)| *other == id) {
+            Some((_, existing)) => *existing = set,
+            None => self.sets.push((id, set)),
+        }
+    }
+
+    /// The bevy system set published under the given id
+    pub fn get_set(&self, id: &StableId) -> Option<InternedSystemSet> {
+        self.sets
+            .iter()
+            .find(|(other, _)| other == id)
+            .map(|(_, set)| *set)
+    }
+}
+
+/// The schedules run once when a mod is loaded, in order
+pub(crate) fn startup_schedules() -> [StableId; 3] {
+    [
+        StableId::from_typed::<common::PreStart>(),
+        StableId::from_typed::<common::Start>(),
+        StableId::from_typed::<common::PostStart>(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+    struct HostSchedule;
+
+    #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+    struct HostSet;
+
+    #[test]
+    fn hosts_register_labels() {
+        let id = StableId::new("host", "HostSchedule");
+        let mut schedules = ModSchedules::default().with(id.clone(), HostSchedule);
+
+        assert!(schedules.contains(&StableId::from_typed::<common::Start>()));
+        assert!(schedules.contains(&StableId::from_typed::<common::FixedUpdate>()));
+        assert_eq!(schedules.get(&id), Some(HostSchedule.intern()));
+        assert!(!schedules.contains(&StableId::new("host", "Unknown")));
+
+        // Registering a label again moves the mod schedule to another bevy schedule
+        schedules.insert(id.clone(), Update);
+        assert_eq!(schedules.get(&id), Some(Update.intern()));
+        assert_eq!(schedules.iter().count(), 6);
+
+        assert!(!ModSchedules::empty().contains(&StableId::from_typed::<common::Update>()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn running_schedules_are_refused() {
+        ModSchedules::default().with(StableId::new("host", "First"), First);
+    }
+
+    #[test]
+    fn hosts_publish_sets() {
+        let id = StableId::new("host", "HostSet");
+        let schedules = ModSchedules::default().with_set(id.clone(), HostSet);
+
+        assert_eq!(schedules.get_set(&id), Some(HostSet.intern()));
+        assert_eq!(schedules.get_set(&StableId::new("host", "Unknown")), None);
+        // Sets are not schedules
+        assert!(!schedules.contains(&id));
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::*;
+use common::{ModPackage, PackageSignature};
+use ed25519_dalek::{Signature, VerifyingKey};
+use tracing::warn;
+
+/// What to do with mods that aren't signed by a trusted signer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignaturePolicy {
+    /// Refuse to load the mod
+    RejectUnsigned,
+    /// Load the mod, but log a warning
+    #[default]
+    WarnUnsigned,
+    /// Load every mod without checking signatures
+    AllowAll,
+}
+
+/// The signers trusted to publish mods, see [`crate::prelude::ModLoaderPlugin`]
+#[derive(Debug, Clone, Default)]
+pub struct TrustStore {
+    pub policy: SignaturePolicy,
+    trusted: Vec<VerifyingKey>,
+}
+
+impl TrustStore {
+    pub fn new(policy: SignaturePolicy) -> Self {
+        Self {
+            policy,
+            trusted: Vec::new(),
+        }
+    }
+
+    /// Trusts mods signed with the given key
+    pub fn with_trusted(mut self, key: VerifyingKey) -> Self {
+        self.trust(key);
+        self
+    }
+
+    pub fn trust(&mut self, key: VerifyingKey) {
+        if !self.trusted.contains(&key) {
+            self.trusted.push(key);
+        }
+    }
+
+    pub fn is_trusted(&self, key: &VerifyingKey) -> bool {
+        self.trusted.contains(key)
+    }
+
+    /// Checks the signature of a package following the policy, returning the trusted signer
+    pub(crate) fn verify(&self, name: &str, package: &ModPackage) -> Result<Option<VerifyingKey>> {
+        let reason = match &package.signature {
+            Some(signature) => match verify_signature(package, signature) {
+                Result::Ok(key) if self.is_trusted(&key) => return Ok(Some(key)),
+                Result::Ok(_) => "signed by an untrusted key",
+                Err(err) if self.policy != SignaturePolicy::AllowAll => {
+                    return Err(err.context(format!("Mod {} has an invalid signature", name)));
+                }
+                Err(_) => "signed with an invalid signature",
+            },
+            None => "unsigned",
+        };
+
+        match self.policy {
+            SignaturePolicy::RejectUnsigned => {
+                bail!("Mod {} is {}, and unsigned mods are rejected", name, reason)
+            }
+            SignaturePolicy::WarnUnsigned => {
+                warn!("Loading mod {} which is {}", name, reason);
+                Ok(None)
+            }
+            SignaturePolicy::AllowAll => Ok(None),
+        }
+    }
+}
+
+fn verify_signature(package: &ModPackage, signature: &PackageSignature) -> Result<VerifyingKey> {
+    let key = VerifyingKey::from_bytes(&signature.public_key)?;
+    let signature = Signature::from_bytes(&signature.signature);
+    key.verify_strict(&package.signed_bytes()?, &signature)?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::*;
+
+    fn signed_package(signing_key: &SigningKey) -> ModPackage {
+        let mut package = ModPackage::new(vec![1, 2, 3], vec![4, 5, 6]);
+        let signature = signing_key.sign(&package.signed_bytes().unwrap());
+        package.signature = Some(PackageSignature {
+            public_key: signing_key.verifying_key().to_bytes(),
+            signature: signature.to_bytes(),
+        });
+        package
+    }
+
+    #[test]
+    fn verify_trusted_signer() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let trust = TrustStore::new(SignaturePolicy::RejectUnsigned)
+            .with_trusted(signing_key.verifying_key());
+
+        let signer = trust.verify("test", &signed_package(&signing_key)).unwrap();
+        assert_eq!(signer, Some(signing_key.verifying_key()));
+    }
+
+    #[test]
+    fn verify_rejects_tampered_package() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let trust = TrustStore::new(SignaturePolicy::WarnUnsigned)
+            .with_trusted(signing_key.verifying_key());
+
+        let mut package = signed_package(&signing_key);
+        package.wasm.push(0);
+        assert!(trust.verify("test", &package).is_err());
+
+        let trust = TrustStore::new(SignaturePolicy::AllowAll);
+        assert_eq!(trust.verify("test", &package).unwrap(), None);
+    }
+
+    #[test]
+    fn verify_follows_policy_for_untrusted_packages() {
+        let untrusted = signed_package(&SigningKey::from_bytes(&[9; 32]));
+        let unsigned = ModPackage::new(vec![1, 2, 3], vec![4, 5, 6]);
+
+        let reject = TrustStore::new(SignaturePolicy::RejectUnsigned);
+        assert!(reject.verify("test", &untrusted).is_err());
+        assert!(reject.verify("test", &unsigned).is_err());
+
+        let warn = TrustStore::new(SignaturePolicy::WarnUnsigned);
+        assert_eq!(warn.verify("test", &untrusted).unwrap(), None);
+        assert_eq!(warn.verify("test", &unsigned).unwrap(), None);
+    }
+}
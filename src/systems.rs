@@ -0,0 +1,638 @@
+use std::{
+    alloc::Layout,
+    borrow::Cow,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use async_channel::Sender;
+use bevy_ecs::{
+    archetype::ArchetypeComponentId,
+    change_detection::{DetectChangesMut, MaybeLocation},
+    component::{ComponentCloneBehavior, ComponentDescriptor, ComponentId, StorageType, Tick},
+    ptr::OwningPtr,
+    query::Access,
+    schedule::{
+        InternedScheduleLabel, InternedSystemSet, IntoScheduleConfigs, Schedules, SystemSet,
+    },
+    system::{Command, Commands, ReadOnlySystem, System, SystemIn, SystemParamValidationError},
+    world::{unsafe_world_cell::UnsafeWorldCell, CommandQueue, DeferredWorld, World},
+};
+use bevy_ecs_macros::Resource;
+use bevy_platform::collections::{HashMap, HashSet};
+use common::StableId;
+use tracing::warn;
+
+use crate::{
+    loaded::{
+        schedule::{HostSetConstraint, HostSetRelation},
+        LoadedMod, RunnerSlot, ScheduleGraph, SystemRun, SystemTrap,
+    },
+    mods::ModHandle,
+    schedules::ModSchedules,
+};
+
+/// How mod systems are run, see [`crate::prelude::ModLoaderPlugin`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionMode {
+    /// Run the systems of every mod from one bevy system per schedule, running mods in
+    /// parallel on the `ComputeTaskPool`. Systems of a single mod run one after the other
+    #[default]
+    Parallel,
+    /// Add each mod system to the host's schedules as a bevy system of its own, so bevy's
+    /// executor interleaves them with host systems and its ambiguity detection covers them
+    ///
+    /// Bevy can't remove systems from schedules, so the systems of unloaded mods stay in them
+    /// and do nothing. Reloaded mods reuse their systems, unless their systems or the order
+    /// they run in changed. Budgets per schedule are not enforced in this mode.
+    BevySystems,
+}
+
+/// Tells apart the systems added for each version of a mod
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// The set of a single system, added for one version of a mod
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct ModSystemSet {
+    generation: u64,
+    system: common::SystemId,
+}
+
+/// The systems added to the host's schedules for each mod, see
+/// [`ExecutionMode::BevySystems`]
+#[derive(Default)]
+pub(crate) struct AddedSystems(HashMap<ModHandle, Vec<Generation>>);
+
+/// The systems of a mod schedule added to a bevy schedule for one version of a mod
+///
+/// Later versions with the same systems, ordered the same way, run in them instead of adding
+/// systems of their own
+struct Generation {
+    label: InternedScheduleLabel,
+    graph: ScheduleGraph,
+    host_set_constraints: Vec<HostSetConstraint>,
+    runner: RunnerSlot,
+}
+
+/// Adds the systems of a newly loaded version of a mod to the host's schedules, ordered
+/// following the mod's dependency graph and around the system sets published by the host
+///
+/// Systems added for a previous version are reused when they didn't change
+pub(crate) fn add_mod_systems(
+    schedules: &mut Schedules,
+    added: &mut AddedSystems,
+    handle: ModHandle,
+    loaded: &LoadedMod,
+    labels: &ModSchedules,
+    traps: &Sender<(ModHandle, SystemTrap)>,
+) {
+    let generations = added.0.entry(handle).or_default();
+
+    let ids: HashSet<&StableId> = loaded
+        .features()
+        .iter()
+        .flat_map(|feature| feature.schedules.ids())
+        .collect();
+    for id in ids {
+        // Startup schedules run once when the mod is loaded, so they have no bevy schedule
+        let Some(label) = labels.get(id) else {
+            continue;
+        };
+
+        let graph = loaded.schedule_graph(id);
+        let host_set_constraints = loaded.host_set_constraints(id);
+        let reused = generations.iter().find(|generation| {
+            generation.label == label
+                && generation.graph == graph
+                && generation.host_set_constraints == host_set_constraints
+        });
+        if let Some(generation) = reused {
+            generation.runner.set(loaded.weak_runner());
+            continue;
+        }
+
+        let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+        let runner = RunnerSlot::new(loaded.weak_runner());
+        let set = |system| ModSystemSet { generation, system };
+        for system in graph.systems.iter().cloned() {
+            let set = set(system.id);
+            schedules.add_systems(
+                label,
+                ModSystem::<()>::new(loaded.name(), handle, set, system, runner.clone(), traps),
+            );
+        }
+        for (before, after) in graph.dependencies.iter() {
+            schedules.configure_sets(label, set(*before).before(set(*after)));
+        }
+        // Each run condition decides on a set of its own, so bevy runs it once per schedule
+        // run no matter how many systems it decides on
+        for condition in graph.conditions.iter().cloned() {
+            let set = set(condition.id);
+            let system = ModSystem::<bool>::new(
+                loaded.name(),
+                handle,
+                set.clone(),
+                condition,
+                runner.clone(),
+                traps,
+            );
+            schedules.configure_sets(label, set.run_if(system));
+        }
+        for (system, condition) in graph.gated.iter() {
+            schedules.configure_sets(label, set(*system).in_set(set(*condition)));
+        }
+        for constraint in host_set_constraints.iter() {
+            let Some(host_set) = labels.get_set(&constraint.set) else {
+                continue;
+            };
+            let set = set(constraint.system);
+            match constraint.relation {
+                HostSetRelation::Before => schedules.configure_sets(label, set.before(host_set)),
+                HostSetRelation::After => schedules.configure_sets(label, set.after(host_set)),
+                HostSetRelation::Within => schedules.configure_sets(label, set.in_set(host_set)),
+            };
+        }
+
+        generations.push(Generation {
+            label,
+            graph,
+            host_set_constraints,
+            runner,
+        });
+    }
+}
+
+/// What the systems of mods return to bevy
+trait ModSystemOutput: Send + Sync + 'static {
+    /// Whether the systems only read the world, like run conditions
+    const READ_ONLY: bool = false;
+
+    /// The output of a system that ran to completion, or that trapped or didn't run
+    fn from_run(run: Option<SystemRun>) -> Self;
+}
+
+impl ModSystemOutput for () {
+    fn from_run(_run: Option<SystemRun>) -> Self {}
+}
+
+/// Returned by run conditions, which count as `false` when they trap
+impl ModSystemOutput for bool {
+    const READ_ONLY: bool = true;
+
+    fn from_run(run: Option<SystemRun>) -> Self {
+        run.is_some_and(|run| run.output)
+    }
+}
+
+/// A system of a mod, run by bevy like any other system
+///
+/// Run conditions of mods are systems returning `bool`
+struct ModSystem<Out> {
+    name: Cow<'static, str>,
+    handle: ModHandle,
+    set: ModSystemSet,
+    /// Name of the placeholder resource standing for the mod's store, which only one system
+    /// of the mod can use at a time
+    store: String,
+    system: common::System,
+    /// Stops working once the mod is unloaded, or replaced by a version that doesn't reuse
+    /// this system
+    runner: RunnerSlot,
+    traps: Sender<(ModHandle, SystemTrap)>,
+    component_access: Access<ComponentId>,
+    archetype_component_access: Access<ArchetypeComponentId>,
+    /// Commands of the system, applied once it finishes
+    queue: CommandQueue,
+    last_run: Tick,
+    output: PhantomData<fn() -> Out>,
+}
+
+impl<Out: ModSystemOutput> ModSystem<Out> {
+    fn new(
+        mod_name: &str,
+        handle: ModHandle,
+        set: ModSystemSet,
+        system: common::System,
+        runner: RunnerSlot,
+        traps: &Sender<(ModHandle, SystemTrap)>,
+    ) -> Self {
+        Self {
+            name: format!("{}::{}", mod_name, system.name).into(),
+            handle,
+            set,
+            store: format!("bevy_harmonize::ModStore({})", mod_name),
+            system,
+            runner,
+            traps: traps.clone(),
+            component_access: Access::default(),
+            archetype_component_access: Access::default(),
+            queue: CommandQueue::default(),
+            last_run: Tick::new(0),
+            output: PhantomData,
+        }
+    }
+}
+
+impl<Out: ModSystemOutput> System for ModSystem<Out> {
+    type In = ();
+    type Out = Out;
+
+    fn name(&self) -> Cow<'static, str> {
+        self.name.clone()
+    }
+
+    fn component_access(&self) -> &Access<ComponentId> {
+        &self.component_access
+    }
+
+    fn archetype_component_access(&self) -> &Access<ArchetypeComponentId> {
+        &self.archetype_component_access
+    }
+
+    fn is_send(&self) -> bool {
+        true
+    }
+
+    fn is_exclusive(&self) -> bool {
+        false
+    }
+
+    fn has_deferred(&self) -> bool {
+        !Out::READ_ONLY
+    }
+
+    unsafe fn run_unsafe(&mut self, _input: SystemIn<'_, Self>, world: UnsafeWorldCell) -> Out {
+        self.last_run = world.increment_change_tick();
+
+        let Some(runner) = self.runner.upgrade() else {
+            return Out::from_run(None);
+        };
+        let mut runner = runner.lock();
+        if runner.suspended || runner.disabled.contains(&self.system.id) {
+            return Out::from_run(None);
+        }
+        let Some(index) = runner.exports.get(&self.system.id).copied() else {
+            return Out::from_run(None);
+        };
+
+        let mut commands = Commands::new_from_entities(&mut self.queue, world.entities());
+        let fuel = runner.budget.per_system;
+        match runner.run_system(index, &mut commands, fuel) {
+            Result::Ok(run) => Out::from_run(Some(run)),
+            Err(error) => {
+                let trap = SystemTrap {
+                    system: self.system.name.clone(),
+                    error,
+                };
+                // The receiver lives as long as the app
+                let _ = self.traps.try_send((self.handle, trap));
+                Out::from_run(None)
+            }
+        }
+    }
+
+    fn apply_deferred(&mut self, world: &mut World) {
+        self.queue.apply(world);
+    }
+
+    fn queue_deferred(&mut self, mut world: DeferredWorld) {
+        world.commands().append(&mut self.queue);
+    }
+
+    unsafe fn validate_param_unsafe(
+        &mut self,
+        _world: UnsafeWorldCell,
+    ) -> Result<(), SystemParamValidationError> {
+        Ok(())
+    }
+
+    fn initialize(&mut self, world: &mut World) {
+        self.last_run = Tick::new(world.change_tick().get().wrapping_sub(Tick::MAX.get()));
+
+        // Run conditions only read the store, the lock of the mod's runner takes turns between
+        // them
+        let (component_id, archetype_component_id) =
+            placeholder_resource(world, self.store.clone());
+        if Out::READ_ONLY {
+            self.component_access.add_resource_read(component_id);
+            self.archetype_component_access
+                .add_resource_read(archetype_component_id);
+        } else {
+            self.component_access.add_resource_write(component_id);
+            self.archetype_component_access
+                .add_resource_write(archetype_component_id);
+        }
+
+        for param in self.system.params.iter() {
+            let common::Param::Res { mutable, id } = param else {
+                continue;
+            };
+            let (component_id, archetype_component_id) =
+                placeholder_resource(world, resource_placeholder_name(id));
+            if *mutable && !Out::READ_ONLY {
+                self.component_access.add_resource_write(component_id);
+                self.archetype_component_access
+                    .add_resource_write(archetype_component_id);
+            } else {
+                self.component_access.add_resource_read(component_id);
+                self.archetype_component_access
+                    .add_resource_read(archetype_component_id);
+            }
+        }
+    }
+
+    fn update_archetype_component_access(&mut self, _world: UnsafeWorldCell) {}
+
+    fn check_change_tick(&mut self, change_tick: Tick) {
+        let age = change_tick.get().wrapping_sub(self.last_run.get());
+        if age > Tick::MAX.get() {
+            warn!("Mod system {} has not run for a long time", self.name);
+            self.last_run = Tick::new(change_tick.get().wrapping_sub(Tick::MAX.get()));
+        }
+    }
+
+    fn default_system_sets(&self) -> Vec<InternedSystemSet> {
+        vec![self.set.intern()]
+    }
+
+    fn get_last_run(&self) -> Tick {
+        self.last_run
+    }
+
+    fn set_last_run(&mut self, last_run: Tick) {
+        self.last_run = last_run;
+    }
+}
+
+// SAFETY: run conditions only declare read access to the world. Mods only reach the world
+// through commands, and conditions trap instead of queuing any, see `ModRunner::conditions`.
+// Their wasm store is owned by the mod rather than the world, and guarded by the runner's lock
+unsafe impl ReadOnlySystem for ModSystem<bool> {}
+
+/// Zero-sized resources standing for data owned by mods, so bevy knows which mod systems
+/// conflict with each other
+#[derive(Resource, Default)]
+struct PlaceholderResources(HashMap<String, (ComponentId, ArchetypeComponentId)>);
+
+fn resource_placeholder_name(id: &StableId) -> String {
+    format!("bevy_harmonize::ModResource({})", id)
+}
+
+/// Returns the id of the resource standing for the mod resource with the given id
+///
+/// It is marked as changed whenever a mod flags the resource as changed, so host systems can
+/// check it with [`World::get_resource_change_ticks_by_id`] like any other resource. It
+/// exists once a mod system accessing the resource was added, or a mod changed it
+pub fn mod_resource_id(world: &World, id: &StableId) -> Option<ComponentId> {
+    let placeholders = world.get_resource::<PlaceholderResources>()?;
+    let (component_id, _) = placeholders.0.get(&resource_placeholder_name(id))?;
+    Some(*component_id)
+}
+
+/// Marks the resource standing for a mod resource as changed
+pub(crate) fn mark_resource_changed(id: StableId) -> impl Command {
+    move |world: &mut World| {
+        let (component_id, _) = placeholder_resource(world, resource_placeholder_name(&id));
+        if let Some(mut resource) = world.get_resource_mut_by_id(component_id) {
+            resource.set_changed();
+        }
+    }
+}
+
+fn placeholder_resource(world: &mut World, name: String) -> (ComponentId, ArchetypeComponentId) {
+    let existing = world
+        .get_resource_or_init::<PlaceholderResources>()
+        .0
+        .get(&name)
+        .copied();
+    if let Some(ids) = existing {
+        return ids;
+    }
+
+    // SAFETY: the resource is zero-sized, so it has nothing to drop and is thread safe
+    let descriptor = unsafe {
+        ComponentDescriptor::new_with_layout(
+            name.clone(),
+            StorageType::Table,
+            Layout::new::<()>(),
+            None,
+            true,
+            ComponentCloneBehavior::Ignore,
+        )
+    };
+    let component_id = world.register_resource_with_descriptor(descriptor);
+    OwningPtr::make((), |ptr| {
+        // SAFETY: the value has the zero-sized layout of the descriptor
+        unsafe { world.insert_resource_by_id(component_id, ptr, MaybeLocation::caller()) }
+    });
+    let archetype_component_id = world
+        .storages()
+        .resources
+        .get(component_id)
+        .expect("Placeholder resource was just inserted")
+        .id();
+
+    let ids = (component_id, archetype_component_id);
+    world
+        .resource_mut::<PlaceholderResources>()
+        .0
+        .insert(name, ids);
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::Update;
+
+    use super::*;
+    use crate::loaded::tests;
+
+    fn mod_system<Out: ModSystemOutput>(
+        mod_name: &str,
+        params: Vec<common::Param>,
+        world: &mut World,
+    ) -> ModSystem<Out> {
+        let (traps, _) = async_channel::unbounded();
+        let system = common::System {
+            id: common::SystemId::from_type(std::any::TypeId::of::<()>()),
+            name: "system".to_owned(),
+            params,
+        };
+        let set = ModSystemSet {
+            generation: 0,
+            system: system.id,
+        };
+        let mut system = ModSystem::new(
+            mod_name,
+            ModHandle(0),
+            set,
+            system,
+            RunnerSlot::default(),
+            &traps,
+        );
+        system.initialize(world);
+        system
+    }
+
+    #[test]
+    fn access_follows_params() {
+        let mut world = World::new();
+        let res = |mutable| common::Param::Res {
+            mutable,
+            id: StableId::new("test", "MyResource"),
+        };
+
+        let read_a = mod_system("a", vec![res(false)], &mut world);
+        let read_b = mod_system("b", vec![res(false)], &mut world);
+        let write_b = mod_system("b", vec![res(true)], &mut world);
+        let write_c = mod_system("c", vec![res(true), common::Param::Command], &mut world);
+        let other_a = mod_system("a", vec![], &mut world);
+
+        let compatible = |a: &ModSystem<()>, b: &ModSystem<()>| {
+            a.component_access().is_compatible(b.component_access())
+        };
+        assert!(compatible(&read_a, &read_b));
+        assert!(!compatible(&read_a, &write_c));
+        assert!(!compatible(&write_b, &write_c));
+        // Systems of the same mod share a store
+        assert!(!compatible(&read_a, &other_a));
+        assert!(!compatible(&read_b, &write_b));
+    }
+
+    #[test]
+    fn conditions_only_read() {
+        let mut world = World::new();
+        let res = common::Param::Res {
+            mutable: false,
+            id: StableId::new("test", "MyResource"),
+        };
+
+        let condition = mod_system::<bool>("a", vec![res.clone()], &mut world);
+        let other_condition = mod_system::<bool>("a", vec![res], &mut world);
+        let system = mod_system::<()>("a", vec![], &mut world);
+
+        assert!(condition
+            .component_access()
+            .is_compatible(other_condition.component_access()));
+        // Systems of the same mod still wait for conditions reading its store
+        assert!(!condition
+            .component_access()
+            .is_compatible(system.component_access()));
+        assert!(!condition.has_deferred());
+    }
+
+    #[test]
+    fn flagged_resources_are_changed_in_the_world() {
+        let mut world = World::new();
+        let id = StableId::new("test", "MyResource");
+        assert_eq!(mod_resource_id(&world, &id), None);
+
+        mark_resource_changed(id.clone()).apply(&mut world);
+        let component_id = mod_resource_id(&world, &id).unwrap();
+        let is_changed = |world: &World, last_run| {
+            let ticks = world.get_resource_change_ticks_by_id(component_id).unwrap();
+            ticks.is_changed(last_run, world.read_change_tick())
+        };
+
+        let last_run = world.increment_change_tick();
+        assert!(!is_changed(&world, last_run));
+
+        world.increment_change_tick();
+        mark_resource_changed(id).apply(&mut world);
+        assert!(is_changed(&world, last_run));
+    }
+
+    /// A mod spawning an entity from each of its systems
+    fn spawning_mod(systems: &[(common::SystemId, &str)]) -> LoadedMod {
+        let wat = r#"(module
+            (import "bevy_harmonize" "spawn_empty" (func $spawn (result i32)))
+            (memory (export "memory") 1)
+            (func (export "run") (param i32) (result i32)
+                call $spawn
+                drop
+                i32.const 0))"#;
+        let mut manifest = tests::manifest(vec![tests::feature("spawning", systems)]);
+        manifest.capabilities = vec![common::Capability::SpawnEntities];
+        tests::load(&tests::settings(), "spawning", manifest, wat).unwrap()
+    }
+
+    #[test]
+    fn systems_of_unloaded_mods_do_nothing() {
+        let mut world = World::new();
+        world.insert_resource(Schedules::default());
+        let (traps, receiver) = async_channel::unbounded();
+        let mut added = AddedSystems::default();
+        let add = |world: &mut World, added: &mut AddedSystems, loaded: &LoadedMod| {
+            let mut schedules = world.resource_mut::<Schedules>();
+            let labels = ModSchedules::default();
+            add_mod_systems(&mut schedules, added, ModHandle(0), loaded, &labels, &traps);
+        };
+        let systems = [(common::SystemId::of::<[u8; 0]>(), "spawn")];
+
+        let loaded = spawning_mod(&systems);
+        add(&mut world, &mut added, &loaded);
+        world.run_schedule(Update);
+        assert_eq!(world.entities().len(), 1);
+
+        drop(loaded);
+        world.run_schedule(Update);
+        assert_eq!(world.entities().len(), 1);
+        assert!(receiver.is_empty());
+    }
+
+    #[test]
+    fn reloaded_mods_reuse_their_systems() {
+        let mut world = World::new();
+        world.insert_resource(Schedules::default());
+        let (traps, _receiver) = async_channel::unbounded();
+        let mut added = AddedSystems::default();
+        // Adds the systems of the mod and runs them, returning how many systems ran
+        let mut run = |world: &mut World, loaded: &LoadedMod| {
+            let mut schedules = world.resource_mut::<Schedules>();
+            let labels = ModSchedules::default();
+            add_mod_systems(
+                &mut schedules,
+                &mut added,
+                ModHandle(0),
+                loaded,
+                &labels,
+                &traps,
+            );
+            let spawned = world.entities().len();
+            world.run_schedule(Update);
+            world.entities().len() - spawned
+        };
+        let systems_len = |world: &World| {
+            let schedules = world.resource::<Schedules>();
+            schedules.get(Update).unwrap().systems_len()
+        };
+        let first = (common::SystemId::of::<[u8; 0]>(), "first");
+        let second = (common::SystemId::of::<[u8; 1]>(), "second");
+
+        let mut loaded = spawning_mod(&[first]);
+        assert_eq!(run(&mut world, &loaded), 1);
+        assert_eq!(systems_len(&world), 1);
+
+        // The same systems run the new version
+        loaded = spawning_mod(&[first]);
+        assert_eq!(run(&mut world, &loaded), 1);
+        assert_eq!(systems_len(&world), 1);
+
+        // Other systems are added next to the old ones, which stop running
+        loaded = spawning_mod(&[first, second]);
+        assert_eq!(run(&mut world, &loaded), 2);
+        assert_eq!(systems_len(&world), 3);
+
+        // Going back to systems added before reuses them
+        loaded = spawning_mod(&[first]);
+        assert_eq!(run(&mut world, &loaded), 1);
+        assert_eq!(systems_len(&world), 3);
+    }
+
+    #[test]
+    fn conditions_of_unloaded_mods_are_false() {
+        let mut world = World::new();
+        let mut condition = mod_system::<bool>("a", vec![], &mut world);
+        assert!(!condition.run((), &mut world));
+    }
+}